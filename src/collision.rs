@@ -0,0 +1,794 @@
+use amethyst::ecs::Entity;
+
+use std::collections::HashMap;
+
+use crate::sidescroller::{GameMap, TileAttributes};
+
+/// Tolerance used when comparing touching edges.
+const EPSILON: f32 = 0.001;
+
+/// Axis-aligned rectangle in world coordinates, y pointing down like in Tiled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32,
+}
+
+impl Rect {
+	pub fn new(x: f32, y: f32, width: f32, height: f32) -> Rect {
+		Rect {
+			x: x,
+			y: y,
+			width: width,
+			height: height,
+		}
+	}
+
+	pub fn from_center(center_x: f32, center_y: f32, width: f32, height: f32) -> Rect {
+		Rect::new(center_x - width / 2.0, center_y - height / 2.0, width, height)
+	}
+
+	pub fn left(&self) -> f32 {
+		self.x
+	}
+
+	pub fn right(&self) -> f32 {
+		self.x + self.width
+	}
+
+	pub fn top(&self) -> f32 {
+		self.y
+	}
+
+	pub fn bottom(&self) -> f32 {
+		self.y + self.height
+	}
+
+	pub fn center_x(&self) -> f32 {
+		self.x + self.width / 2.0
+	}
+
+	pub fn center_y(&self) -> f32 {
+		self.y + self.height / 2.0
+	}
+
+	pub fn translate(&self, dx: f32, dy: f32) -> Rect {
+		Rect::new(self.x + dx, self.y + dy, self.width, self.height)
+	}
+
+	/// Rectangles that only share an edge are not overlapping.
+	pub fn overlaps(&self, other: &Rect) -> bool {
+		self.left() < other.right()
+			&& self.right() > other.left()
+			&& self.top() < other.bottom()
+			&& self.bottom() > other.top()
+	}
+
+	/// Bounding box of a list of points.
+	pub fn bounding(points: &[(f32, f32)]) -> Option<Rect> {
+		let first = points.first()?;
+
+		let (mut min_x, mut min_y, mut max_x, mut max_y) = (first.0, first.1, first.0, first.1);
+
+		for point in points {
+			min_x = min_x.min(point.0);
+			min_y = min_y.min(point.1);
+			max_x = max_x.max(point.0);
+			max_y = max_y.max(point.1);
+		}
+
+		Some(Rect::new(min_x, min_y, max_x - min_x, max_y - min_y))
+	}
+}
+
+/// Range of tile coordinates covered by the area, clamped to the map.
+pub fn tile_range(game_map: &GameMap, area: &Rect) -> (usize, usize, usize, usize) {
+	let tile_size = game_map.tile_size as f32;
+
+	let clamp = |value: f32, max: usize| -> usize {
+		if value < 0.0 {
+			0
+		} else {
+			(value as usize).min(max - 1)
+		}
+	};
+
+	(
+		clamp((area.left() / tile_size).floor(), game_map.width),
+		clamp((area.top() / tile_size).floor(), game_map.height),
+		clamp((area.right() / tile_size).floor(), game_map.width),
+		clamp((area.bottom() / tile_size).floor(), game_map.height),
+	)
+}
+
+/// Hitboxes of all collision tiles touching the area whose attributes match the filter.
+pub fn tile_rects<F>(game_map: &GameMap, area: &Rect, filter: F) -> Vec<Rect>
+where
+	F: Fn(&TileAttributes) -> bool,
+{
+	let mut rects = Vec::new();
+
+	let (min_x, min_y, max_x, max_y) = tile_range(game_map, area);
+
+	for y in min_y..=max_y {
+		for x in min_x..=max_x {
+			if filter(&game_map.get_collision_tile(x, y)) {
+				rects.extend(game_map.get_tile_hitboxes(x, y));
+			}
+		}
+	}
+
+	rects
+}
+
+pub fn solid_rects(game_map: &GameMap, area: &Rect) -> Vec<Rect> {
+	tile_rects(game_map, area, |attributes| attributes.solid && attributes.slope.is_none())
+}
+
+/// Whether the area overlaps any collision tile matching the filter.
+pub fn overlaps_tiles<F>(game_map: &GameMap, area: &Rect, filter: F) -> bool
+where
+	F: Fn(&TileAttributes) -> bool,
+{
+	tile_rects(game_map, area, filter)
+		.iter()
+		.any(|rect| rect.overlaps(area))
+}
+
+/// Walks the tile grid along the line between the two points and checks that
+/// no solid collision tile is in the way.
+pub fn line_of_sight(game_map: &GameMap, from: (f32, f32), to: (f32, f32)) -> bool {
+	let tile_size = game_map.tile_size as f32;
+
+	let mut tile_x = (from.0 / tile_size).floor() as i32;
+	let mut tile_y = (from.1 / tile_size).floor() as i32;
+
+	let dx = to.0 - from.0;
+	let dy = to.1 - from.1;
+
+	let step_x = if dx > 0.0 { 1 } else { -1 };
+	let step_y = if dy > 0.0 { 1 } else { -1 };
+
+	// Fraction of the line between crossing two vertical or horizontal tile edges
+	let delta_x = if dx != 0.0 { tile_size / dx.abs() } else { std::f32::INFINITY };
+	let delta_y = if dy != 0.0 { tile_size / dy.abs() } else { std::f32::INFINITY };
+
+	// Fraction of the line where it crosses the next vertical or horizontal tile edge
+	let mut next_x = if dx > 0.0 {
+		((tile_x + 1) as f32 * tile_size - from.0) / dx
+	} else if dx < 0.0 {
+		(tile_x as f32 * tile_size - from.0) / dx
+	} else {
+		std::f32::INFINITY
+	};
+	let mut next_y = if dy > 0.0 {
+		((tile_y + 1) as f32 * tile_size - from.1) / dy
+	} else if dy < 0.0 {
+		(tile_y as f32 * tile_size - from.1) / dy
+	} else {
+		std::f32::INFINITY
+	};
+
+	loop {
+		if tile_x >= 0 && tile_y >= 0 {
+			let attributes = game_map.get_collision_tile(tile_x as usize, tile_y as usize);
+
+			if attributes.solid && attributes.slope.is_none() {
+				return false;
+			}
+		}
+
+		// The line ends inside this tile
+		if next_x > 1.0 && next_y > 1.0 {
+			return true;
+		}
+
+		if next_x < next_y {
+			next_x += delta_x;
+			tile_x += step_x;
+		} else {
+			next_y += delta_y;
+			tile_y += step_y;
+		}
+	}
+}
+
+/// Whether there is something to stand on just past the bottom front corner of
+/// the rectangle when walking in `direction`. Whole collision tiles are checked,
+/// so insets of the tile hitboxes don't show up as gaps between floor tiles.
+pub fn ground_ahead(game_map: &GameMap, rect: &Rect, direction: f32) -> bool {
+	let tile_size = game_map.tile_size as f32;
+	let front = if direction < 0.0 { rect.left() - 1.0 } else { rect.right() + 1.0 };
+
+	if front < 0.0 {
+		return false;
+	}
+
+	let tile_x = (front / tile_size) as usize;
+	let feet_y = ((rect.bottom() - 1.0).max(0.0) / tile_size) as usize;
+	let below_y = ((rect.bottom() + 1.0) / tile_size) as usize;
+
+	let below = game_map.get_collision_tile(tile_x, below_y);
+
+	// A slope rising in front of the feet continues the ground as well
+	below.solid || below.one_way || below.slope.is_some() || game_map.get_collision_tile(tile_x, feet_y).slope.is_some()
+}
+
+/// Platforms that only block actors landing on them from above.
+pub fn one_way_rects(game_map: &GameMap, area: &Rect) -> Vec<Rect> {
+	tile_rects(game_map, area, |attributes| attributes.one_way && !attributes.solid)
+}
+
+/// Surfaces an actor touched during its last move. `bottom` means the actor is
+/// resting on something, `left` and `right` are walls and `top` is a ceiling.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Contacts {
+	pub left: bool,
+	pub right: bool,
+	pub top: bool,
+	pub bottom: bool,
+}
+
+impl Contacts {
+	/// Normals of the touched surfaces, pointing away from the surface towards the actor.
+	pub fn normals(&self) -> Vec<(f32, f32)> {
+		let mut normals = Vec::new();
+
+		if self.left {
+			normals.push((1.0, 0.0));
+		}
+		if self.right {
+			normals.push((-1.0, 0.0));
+		}
+		if self.top {
+			normals.push((0.0, 1.0));
+		}
+		if self.bottom {
+			normals.push((0.0, -1.0));
+		}
+
+		normals
+	}
+}
+
+/// Moves the rectangle horizontally as far as it can go towards `dx`, returning
+/// the distance moved and whether a solid stopped it. Every tile between the start
+/// and the end position is tested, so fast movement cannot tunnel through walls.
+/// `dynamic_solids` are tested alongside the tile grid.
+pub fn sweep_x(game_map: &GameMap, rect: &Rect, dx: f32, dynamic_solids: &[Rect]) -> (f32, bool) {
+	if dx == 0.0 {
+		return (0.0, false);
+	}
+
+	let swept = if dx > 0.0 {
+		Rect::new(rect.x, rect.y, rect.width + dx, rect.height)
+	} else {
+		Rect::new(rect.x + dx, rect.y, rect.width - dx, rect.height)
+	};
+
+	let mut allowed = dx;
+
+	let mut blocking = solid_rects(game_map, &swept);
+	blocking.extend(dynamic_solids.iter().filter(|solid| solid.overlaps(&swept)));
+
+	for solid in blocking {
+		// Only solids beside the rectangle block it, ones it is already inside are ignored
+		if solid.top() >= rect.bottom() - EPSILON || solid.bottom() <= rect.top() + EPSILON {
+			continue;
+		}
+
+		if dx > 0.0 && solid.left() >= rect.right() - EPSILON {
+			allowed = allowed.min((solid.left() - rect.right()).max(0.0));
+		} else if dx < 0.0 && solid.right() <= rect.left() + EPSILON {
+			allowed = allowed.max((solid.right() - rect.left()).min(0.0));
+		}
+	}
+
+	// Ending up right against a solid after the full move isn't a hit yet
+	let hit = allowed.abs() < dx.abs() - EPSILON;
+
+	(allowed, hit)
+}
+
+/// Vertical counterpart of `sweep_x`. One-way platforms block downward movement
+/// when `one_way` is set and the rectangle starts above them.
+pub fn sweep_y(game_map: &GameMap, rect: &Rect, dy: f32, one_way: bool, dynamic_solids: &[Rect]) -> (f32, bool) {
+	if dy == 0.0 {
+		return (0.0, false);
+	}
+
+	let swept = if dy > 0.0 {
+		Rect::new(rect.x, rect.y, rect.width, rect.height + dy)
+	} else {
+		Rect::new(rect.x, rect.y + dy, rect.width, rect.height - dy)
+	};
+
+	let mut allowed = dy;
+	let mut hit = false;
+
+	let mut blocking = solid_rects(game_map, &swept);
+	blocking.extend(dynamic_solids.iter().filter(|solid| solid.overlaps(&swept)));
+
+	if one_way && dy > 0.0 {
+		blocking.extend(one_way_rects(game_map, &swept));
+	}
+
+	for solid in blocking {
+		if solid.left() >= rect.right() - EPSILON || solid.right() <= rect.left() + EPSILON {
+			continue;
+		}
+
+		if dy > 0.0 && solid.top() >= rect.bottom() - EPSILON {
+			let distance = (solid.top() - rect.bottom()).max(0.0);
+			if distance <= allowed {
+				allowed = distance;
+				hit = true;
+			}
+		} else if dy < 0.0 && solid.bottom() <= rect.top() + EPSILON {
+			let distance = (solid.bottom() - rect.top()).min(0.0);
+			if distance >= allowed {
+				allowed = distance;
+				hit = true;
+			}
+		}
+	}
+
+	(allowed, hit)
+}
+
+/// Height of the slope surface at world position `x`, if the collision tile at
+/// the given tile coordinates is a slope.
+pub fn slope_surface(game_map: &GameMap, tile_x: usize, tile_y: usize, x: f32) -> Option<f32> {
+	let (left, right) = game_map.get_collision_tile(tile_x, tile_y).slope?;
+
+	let tile_size = game_map.tile_size as f32;
+	let offset = ((x - tile_x as f32 * tile_size) / tile_size).max(0.0).min(1.0);
+
+	Some((tile_y + 1) as f32 * tile_size - tile_size * (left + (right - left) * offset))
+}
+
+/// The slope surface under the bottom centre of the rectangle that is closest to
+/// its bottom edge, searching up to a tile above and `below` pixels under it.
+pub fn find_slope_surface(game_map: &GameMap, rect: &Rect, below: f32) -> Option<f32> {
+	let tile_size = game_map.tile_size as f32;
+	let x = rect.center_x();
+
+	if x < 0.0 || rect.bottom() < 0.0 {
+		return None;
+	}
+
+	let tile_x = (x / tile_size).floor() as usize;
+	let first_row = ((rect.bottom() - tile_size).max(0.0) / tile_size).floor() as usize;
+	let last_row = ((rect.bottom() + below) / tile_size).floor() as usize;
+
+	let mut closest: Option<f32> = None;
+
+	for tile_y in first_row..=last_row {
+		if let Some(surface) = slope_surface(game_map, tile_x, tile_y, x) {
+			if surface < rect.bottom() - tile_size || surface > rect.bottom() + below {
+				continue;
+			}
+
+			let is_closer = match closest {
+				Some(closest) => (surface - rect.bottom()).abs() < (closest - rect.bottom()).abs(),
+				None => true,
+			};
+
+			if is_closer {
+				closest = Some(surface);
+			}
+		}
+	}
+
+	closest
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MoveOptions {
+	/// Collide with one-way platforms.
+	pub one_way: bool,
+	/// The actor was standing before the move, so it sticks to slopes while walking down them.
+	pub grounded: bool,
+}
+
+/// Moves the rectangle by `dx` and `dy` against the tile grid and the dynamic solids,
+/// one axis at a time, and reports which surfaces it ended up touching.
+pub fn move_and_collide(
+	game_map: &GameMap,
+	rect: &Rect,
+	dx: f32,
+	dy: f32,
+	options: MoveOptions,
+	dynamic_solids: &[Rect],
+) -> (Rect, Contacts) {
+	let mut contacts = Contacts::default();
+	let tile_size = game_map.tile_size as f32;
+
+	// On a slope the lower half of the actor is below the surface next to it, so let
+	// it step up onto solid tiles at the top of the slope instead of stopping there.
+	// A 45 degree slope also rises by the distance moved before the step is reached.
+	let step_height = if options.grounded && find_slope_surface(game_map, rect, 1.0).is_some() {
+		rect.width / 2.0 + dx.abs()
+	} else {
+		0.0
+	};
+
+	let sweep_rect = Rect::new(rect.x, rect.y, rect.width, rect.height - step_height);
+	let (moved_x, hit_x) = sweep_x(game_map, &sweep_rect, dx, dynamic_solids);
+	let mut rect = rect.translate(moved_x, 0.0);
+	contacts.left = hit_x && dx < 0.0;
+	contacts.right = hit_x && dx > 0.0;
+
+	if step_height > 0.0 {
+		let step_top = solid_rects(game_map, &rect)
+			.iter()
+			.filter(|solid| solid.overlaps(&rect))
+			.map(|solid| solid.top())
+			.fold(rect.bottom(), f32::min);
+
+		rect = rect.translate(0.0, step_top - rect.bottom());
+	}
+
+	let (moved_y, hit_y) = sweep_y(game_map, &rect, dy, options.one_way, dynamic_solids);
+	rect = rect.translate(0.0, moved_y);
+	contacts.top = hit_y && dy < 0.0;
+	contacts.bottom = hit_y && dy > 0.0;
+
+	// Slopes are resolved against the bottom centre of the rectangle. Grounded actors
+	// are also snapped down to the surface so they don't bounce while walking down.
+	let snap = if options.grounded { tile_size / 2.0 } else { 0.0 };
+
+	if dy >= 0.0 {
+		if let Some(surface) = find_slope_surface(game_map, &rect, snap) {
+			if surface <= rect.bottom() || options.grounded {
+				rect = rect.translate(0.0, surface - rect.bottom());
+				contacts.bottom = true;
+			}
+		}
+	}
+
+	// Grounded actors stick to flat ground just below them as well, like where a
+	// slope ends on a floor the bottom centre has already passed
+	if snap > 0.0 && dy >= 0.0 && !contacts.bottom {
+		let (distance, hit) = sweep_y(game_map, &rect, snap, options.one_way, dynamic_solids);
+
+		if hit {
+			rect = rect.translate(0.0, distance);
+			contacts.bottom = true;
+		}
+	}
+
+	// Resting on the ground counts as a contact even without moving into it
+	if !contacts.bottom && dy >= 0.0 {
+		let (probe, hit) = sweep_y(game_map, &rect, 1.0, options.one_way, dynamic_solids);
+		contacts.bottom = hit && probe < EPSILON;
+	}
+
+	(rect, contacts)
+}
+
+/// Two actors whose bounding boxes overlap, published by the `BroadphaseSystem`.
+#[derive(Debug, Clone)]
+pub struct OverlapEvent {
+	pub a: Entity,
+	pub b: Entity,
+}
+
+/// Broadphase for actor-to-actor collision. Actors are bucketed by the map tiles
+/// their bounding boxes cover, so a query only tests actors in nearby cells.
+#[derive(Default)]
+pub struct SpatialHash {
+	cell_size: f32,
+	cells: HashMap<(i32, i32), Vec<usize>>,
+	entries: Vec<(Entity, Rect)>,
+}
+
+impl SpatialHash {
+	/// Empties the hash, keeping the allocated buckets for the next rebuild.
+	pub fn clear(&mut self, cell_size: f32) {
+		self.cell_size = cell_size;
+		self.entries.clear();
+
+		for cell in self.cells.values_mut() {
+			cell.clear();
+		}
+	}
+
+	pub fn insert(&mut self, entity: Entity, rect: Rect) {
+		let index = self.entries.len();
+		self.entries.push((entity, rect));
+
+		let (min_x, min_y, max_x, max_y) = self.cell_range(&rect);
+
+		for y in min_y..=max_y {
+			for x in min_x..=max_x {
+				self.cells.entry((x, y)).or_insert_with(Vec::new).push(index);
+			}
+		}
+	}
+
+	/// Actors overlapping the area.
+	pub fn query(&self, area: &Rect) -> Vec<(Entity, Rect)> {
+		let (min_x, min_y, max_x, max_y) = self.cell_range(area);
+
+		let mut found: Vec<usize> = Vec::new();
+
+		for y in min_y..=max_y {
+			for x in min_x..=max_x {
+				if let Some(cell) = self.cells.get(&(x, y)) {
+					found.extend(cell.iter().cloned().filter(|index| self.entries[*index].1.overlaps(area)));
+				}
+			}
+		}
+
+		// Actors covering several cells are found once per cell
+		found.sort();
+		found.dedup();
+
+		found.iter().map(|index| self.entries[*index]).collect()
+	}
+
+	/// Every pair of overlapping actors, each pair reported once.
+	pub fn overlapping_pairs(&self) -> Vec<(Entity, Entity)> {
+		let mut pairs = Vec::new();
+
+		for cell in self.cells.values() {
+			for (i, first) in cell.iter().enumerate() {
+				for second in &cell[i + 1..] {
+					if self.entries[*first].1.overlaps(&self.entries[*second].1) {
+						pairs.push((*first.min(second), *first.max(second)));
+					}
+				}
+			}
+		}
+
+		pairs.sort();
+		pairs.dedup();
+
+		pairs
+			.iter()
+			.map(|(first, second)| (self.entries[*first].0, self.entries[*second].0))
+			.collect()
+	}
+
+	fn cell_range(&self, rect: &Rect) -> (i32, i32, i32, i32) {
+		(
+			(rect.left() / self.cell_size).floor() as i32,
+			(rect.top() / self.cell_size).floor() as i32,
+			(rect.right() / self.cell_size).floor() as i32,
+			(rect.bottom() / self.cell_size).floor() as i32,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use amethyst::ecs::{Builder, World};
+
+	use std::time::{Duration, Instant};
+
+	use crate::sidescroller::MapLayer;
+
+	const ACTORS: usize = 500;
+	const TICKS: u32 = 600;
+
+	const TILE_SIZE: f32 = 32.0;
+	const WALK_SPEED: f32 = 180.0;
+	const DELTA_SECONDS: f32 = 1.0 / 60.0;
+
+	/// Map drawn with `#` for solid tiles, `/` and `\\` for 45 degree slopes rising to the
+	/// right and to the left, and `<` `>` / `{` `}` for the halves of 22.5 degree slopes.
+	fn game_map(rows: &[&str]) -> GameMap {
+		let tiles: Vec<Vec<u32>> = rows
+			.iter()
+			.map(|row| {
+				row.chars()
+					.map(|tile| match tile {
+						'#' => 1,
+						'/' => 2,
+						'\\' => 3,
+						'<' => 4,
+						'>' => 5,
+						'{' => 6,
+						'}' => 7,
+						_ => 0,
+					})
+					.collect()
+			})
+			.collect();
+
+		let slopes = [(0.0, 1.0), (1.0, 0.0), (0.0, 0.5), (0.5, 1.0), (1.0, 0.5), (0.5, 0.0)];
+
+		let mut tile_attributes = HashMap::new();
+		tile_attributes.insert(1, TileAttributes { solid: true, ..TileAttributes::default() });
+
+		for (gid, slope) in (2..).zip(slopes.iter()) {
+			tile_attributes.insert(
+				gid,
+				TileAttributes {
+					solid: true,
+					slope: Some(*slope),
+					..TileAttributes::default()
+				},
+			);
+		}
+
+		GameMap {
+			level: String::from("test"),
+			width: tiles[0].len(),
+			height: tiles.len(),
+			tile_size: TILE_SIZE as usize,
+			layers: vec![MapLayer {
+				name: String::from("collision"),
+				properties: HashMap::new(),
+				tiles: tiles,
+			}],
+			collision_layer: Some(0),
+			tile_attributes: tile_attributes,
+			tile_hitboxes: HashMap::new(),
+			actors: Vec::new(),
+			exits: Vec::new(),
+			platforms: Vec::new(),
+			spawners: Vec::new(),
+			spawn_points: HashMap::new(),
+		}
+	}
+
+	/// Walks a one tile actor standing at `(x, bottom)` in `direction` at a constant speed,
+	/// falling whenever it isn't grounded, and returns its rect and contacts every tick.
+	fn walk(game_map: &GameMap, x: f32, bottom: f32, direction: f32, ticks: usize) -> Vec<(Rect, Contacts)> {
+		let mut rect = Rect::new(x - TILE_SIZE / 2.0, bottom - TILE_SIZE, TILE_SIZE, TILE_SIZE);
+		let mut grounded = true;
+		let mut fall_speed = 0.0;
+		let mut steps = Vec::new();
+
+		for _ in 0..ticks {
+			let dy = if grounded { 0.0 } else { fall_speed * DELTA_SECONDS };
+			let options = MoveOptions { one_way: true, grounded: grounded };
+			let (moved, contacts) = move_and_collide(game_map, &rect, direction * WALK_SPEED * DELTA_SECONDS, dy, options, &[]);
+
+			grounded = contacts.bottom;
+			fall_speed = if grounded { 0.0 } else { fall_speed + 1000.0 * DELTA_SECONDS };
+			rect = moved;
+			steps.push((rect, contacts));
+		}
+
+		steps
+	}
+
+	#[test]
+	fn sweep_x_only_reports_a_hit_when_the_move_is_cut_short() {
+		let game_map = game_map(&["..#", "###"]);
+		let rect = Rect::new(0.0, 0.0, TILE_SIZE, TILE_SIZE);
+
+		assert_eq!(sweep_x(&game_map, &rect, TILE_SIZE, &[]), (TILE_SIZE, false));
+		assert_eq!(sweep_x(&game_map, &rect, TILE_SIZE + 4.0, &[]), (TILE_SIZE, true));
+	}
+
+	#[test]
+	fn walking_into_a_wall_stops_against_it() {
+		let game_map = game_map(&["......", "....##", "######"]);
+		let steps = walk(&game_map, 16.0, 64.0, 1.0, 60);
+		let (rect, contacts) = steps[steps.len() - 1];
+
+		assert!(contacts.right);
+		assert!(contacts.bottom);
+		assert_eq!(rect.right(), 128.0);
+	}
+
+	#[test]
+	fn walking_down_a_slope_onto_the_floor_stays_grounded() {
+		let game_map = game_map(&["..........", "##\\.......", "##########"]);
+		let steps = walk(&game_map, 16.0, 32.0, 1.0, 80);
+
+		assert!(steps.iter().all(|(_, contacts)| contacts.bottom && !contacts.right));
+		assert_eq!(steps[steps.len() - 1].0.bottom(), 64.0);
+	}
+
+	#[test]
+	fn walking_down_a_shallow_slope_onto_the_floor_stays_grounded() {
+		let game_map = game_map(&["..........", "##{}......", "##########"]);
+		let steps = walk(&game_map, 16.0, 32.0, 1.0, 80);
+
+		assert!(steps.iter().all(|(_, contacts)| contacts.bottom && !contacts.right));
+		assert_eq!(steps[steps.len() - 1].0.bottom(), 64.0);
+	}
+
+	#[test]
+	fn walking_up_a_slope_passes_onto_the_block_at_its_top() {
+		let game_map = game_map(&["..........", "...../####", "##########"]);
+		let steps = walk(&game_map, 16.0, 64.0, 1.0, 80);
+
+		assert!(steps.iter().all(|(_, contacts)| contacts.bottom && !contacts.right));
+
+		let (rect, _) = steps[steps.len() - 1];
+		assert!(rect.left() > 192.0);
+		assert_eq!(rect.bottom(), 32.0);
+	}
+
+	#[test]
+	fn walking_up_a_shallow_slope_passes_onto_the_block_at_its_top() {
+		let game_map = game_map(&["..........", "....<>####", "##########"]);
+		let steps = walk(&game_map, 16.0, 64.0, 1.0, 80);
+
+		assert!(steps.iter().all(|(_, contacts)| contacts.bottom && !contacts.right));
+
+		let (rect, _) = steps[steps.len() - 1];
+		assert!(rect.left() > 192.0);
+		assert_eq!(rect.bottom(), 32.0);
+	}
+
+	#[test]
+	fn walking_off_a_ledge_falls() {
+		let game_map = game_map(&["..........", "##........", "##########"]);
+		let steps = walk(&game_map, 16.0, 32.0, 1.0, 80);
+
+		assert!(steps.iter().any(|(_, contacts)| !contacts.bottom));
+		assert_eq!(steps[steps.len() - 1].0.bottom(), 64.0);
+	}
+
+	#[test]
+	fn falling_onto_a_slope_lands_on_its_surface() {
+		let game_map = game_map(&["...", "...", "./#", "###"]);
+		let rect = Rect::from_center(48.0, 16.0, TILE_SIZE, TILE_SIZE);
+		let options = MoveOptions { one_way: true, grounded: false };
+		let (rect, contacts) = move_and_collide(&game_map, &rect, 0.0, 64.0, options, &[]);
+
+		assert!(contacts.bottom);
+		assert_eq!(rect.bottom(), 80.0);
+	}
+
+	/// Rebuilds the hash with 500 actors spread over a 40x30 tile map every tick,
+	/// finds the overlapping pairs and queries around every actor, like the
+	/// broadphase and damage systems do. A tick has to fit comfortably in 1/60 s.
+	/// Run with `cargo test --release -- --ignored spatial_hash_keeps_up`.
+	#[test]
+	#[ignore]
+	fn spatial_hash_keeps_up_with_500_actors_at_60_hz() {
+		let mut world = World::new();
+		let entities: Vec<Entity> = (0..ACTORS).map(|_| world.create_entity().build()).collect();
+
+		// Small linear congruential generator so every run uses the same layout
+		let mut seed: u32 = 12345;
+		let mut next = move || {
+			seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+			(seed >> 16) as f32 / 65536.0
+		};
+
+		let mut rects: Vec<Rect> = (0..ACTORS)
+			.map(|_| Rect::new(next() * 1248.0, next() * 928.0, 32.0, 32.0))
+			.collect();
+
+		let mut spatial_hash = SpatialHash::default();
+		let mut found = 0;
+
+		let start = Instant::now();
+
+		for tick in 0..TICKS {
+			let step = if tick % 2 == 0 { 1.0 } else { -1.0 };
+
+			spatial_hash.clear(32.0);
+
+			for (entity, rect) in entities.iter().zip(rects.iter_mut()) {
+				*rect = rect.translate(step, 0.0);
+				spatial_hash.insert(*entity, *rect);
+			}
+
+			found += spatial_hash.overlapping_pairs().len();
+
+			for rect in &rects {
+				found += spatial_hash.query(rect).len();
+			}
+		}
+
+		let per_tick = start.elapsed() / TICKS;
+		let budget = Duration::from_micros(1_000_000 / 60 / 4);
+
+		assert!(found > 0);
+		assert!(per_tick < budget, "{:?} per tick, budget is {:?}", per_tick, budget);
+	}
+}
@@ -1,20 +1,117 @@
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LevelConfig {
+	pub name: String,
+	pub file: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct MapConfig {
 	pub map_path: String,
+	pub start_level: String,
+	pub levels: Vec<LevelConfig>,
 }
 
 impl Default for MapConfig {
 	fn default() -> Self {
 		MapConfig {
 			map_path: String::from("/"),
+			start_level: String::from("test1"),
+			levels: vec![LevelConfig {
+				name: String::from("test1"),
+				file: String::from("test1.tmx"),
+			}],
+		}
+	}
+}
+
+impl MapConfig {
+	pub fn get_level(&self, name: &str) -> Option<&LevelConfig> {
+		self.levels.iter().find(|level| level.name == name)
+	}
+}
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+	pub tick_rate: u32,
+	pub max_ticks_per_frame: u32,
+	/// Seed for gameplay randomness, a random one is picked when unset.
+	pub seed: Option<u64>,
+}
+
+impl Default for SimulationConfig {
+	fn default() -> Self {
+		SimulationConfig {
+			tick_rate: 60,
+			max_ticks_per_frame: 5,
+			seed: None,
 		}
 	}
 }
 
 
+/// Enemy kind that Tiled enemy objects pick with their `archetype` property.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EnemyArchetype {
+	pub name: String,
+	pub texture: String,
+	pub sprite_sheet: String,
+	pub width: f32,
+	pub height: f32,
+	pub health: u32,
+	pub speed: f32,
+	pub jump_velocity: f32,
+	/// One of "idle", "jump" or "patrol".
+	pub behaviour: String,
+	pub turn_at_ledges: bool,
+	pub sight_range: f32,
+	pub give_up_time: f32,
+	/// Damage dealt to the player on contact.
+	pub damage: u32,
+}
+
+impl Default for EnemyArchetype {
+	fn default() -> Self {
+		EnemyArchetype {
+			name: String::from("default"),
+			texture: String::from("sprites/enemy.png"),
+			sprite_sheet: String::from("sprites/enemy_spritesheet.ron"),
+			width: 32.0,
+			height: 32.0,
+			health: 1,
+			speed: 80.0,
+			jump_velocity: -600.0,
+			behaviour: String::from("patrol"),
+			turn_at_ledges: true,
+			sight_range: 200.0,
+			give_up_time: 2.0,
+			damage: 1,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ArchetypeConfig {
+	pub archetypes: Vec<EnemyArchetype>,
+}
+
+impl ArchetypeConfig {
+	pub fn get_archetype(&self, name: &str) -> Option<&EnemyArchetype> {
+		self.archetypes.iter().find(|archetype| archetype.name == name)
+	}
+}
+
+
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct GeneralConfig {
 	
     pub map: MapConfig,
 
+    #[serde(default)]
+    pub simulation: SimulationConfig,
+
 }
@@ -1,16 +1,44 @@
 use amethyst::core::{SystemBundle, ArcThreadPool};
+use amethyst::core::timing::Time;
 use amethyst::ecs::prelude::{Dispatcher, DispatcherBuilder, System, World};
 use amethyst::{DataInit, Error, Result};
 
+/// Progress of the fixed update between two ticks, from 0.0 to 1.0.
+/// Rendering can use it to interpolate between the last two simulated states.
+#[derive(Debug, Default)]
+pub struct FixedUpdate {
+    pub alpha: f32,
+}
+
 pub struct CustomGameData<'a, 'b> {
     pub base: Dispatcher<'a, 'b>,
     pub running: Dispatcher<'a, 'b>,
+    pub fixed: Dispatcher<'a, 'b>,
+    fixed_seconds: f32,
+    max_ticks_per_frame: u32,
+    accumulator: f32,
 }
 
 impl<'a, 'b> CustomGameData<'a, 'b> {
     /// Update game data
     pub fn update(&mut self, world: &World, running: bool) {
         if running {
+            self.accumulator += world.read_resource::<Time>().delta_seconds();
+
+            let mut ticks = 0;
+            while self.accumulator >= self.fixed_seconds && ticks < self.max_ticks_per_frame {
+                self.fixed.dispatch(&world.res);
+                self.accumulator -= self.fixed_seconds;
+                ticks += 1;
+            }
+
+            // Drop the time we couldn't catch up on instead of falling further behind
+            if self.accumulator >= self.fixed_seconds {
+                self.accumulator %= self.fixed_seconds;
+            }
+
+            world.write_resource::<FixedUpdate>().alpha = self.accumulator / self.fixed_seconds;
+
             self.running.dispatch(&world.res);
         }
         self.base.dispatch(&world.res);
@@ -20,6 +48,9 @@ impl<'a, 'b> CustomGameData<'a, 'b> {
 pub struct CustomGameDataBuilder<'a, 'b> {
     pub base: DispatcherBuilder<'a, 'b>,
     pub running: DispatcherBuilder<'a, 'b>,
+    pub fixed: DispatcherBuilder<'a, 'b>,
+    pub tick_rate: u32,
+    pub max_ticks_per_frame: u32,
 }
 
 impl<'a, 'b> Default for CustomGameDataBuilder<'a, 'b> {
@@ -33,6 +64,9 @@ impl<'a, 'b> CustomGameDataBuilder<'a, 'b> {
         CustomGameDataBuilder {
             base: DispatcherBuilder::new(),
             running: DispatcherBuilder::new(),
+            fixed: DispatcherBuilder::new(),
+            tick_rate: 60,
+            max_ticks_per_frame: 5,
         }
     }
 
@@ -61,6 +95,24 @@ impl<'a, 'b> CustomGameDataBuilder<'a, 'b> {
         self.running.add(system, name, dependencies);
         self
     }
+
+    /// Adds a system that runs at the fixed tick rate while the game is running.
+    /// Fixed systems should step by `Time::fixed_seconds` instead of the frame delta.
+    pub fn with_fixed<S>(mut self, system: S, name: &str, dependencies: &[&str]) -> Self
+    where
+        for<'c> S: System<'c> + Send + 'a,
+    {
+        self.fixed.add(system, name, dependencies);
+        self
+    }
+
+    /// Sets the ticks per second of the fixed systems and how many ticks a single
+    /// frame may run to catch up after a slow frame.
+    pub fn with_tick_rate(mut self, tick_rate: u32, max_ticks_per_frame: u32) -> Self {
+        self.tick_rate = tick_rate;
+        self.max_ticks_per_frame = max_ticks_per_frame;
+        self
+    }
 }
 
 impl<'a, 'b> DataInit<CustomGameData<'a, 'b>> for CustomGameDataBuilder<'a, 'b> {
@@ -80,6 +132,23 @@ impl<'a, 'b> DataInit<CustomGameData<'a, 'b>> for CustomGameDataBuilder<'a, 'b>
         let mut running = self.running.build();
         running.setup(&mut world.res);
 
-        CustomGameData { base, running }
+        #[cfg(not(no_threading))]
+        let mut fixed = self.fixed.with_pool(pool.clone()).build();
+        #[cfg(no_threading)]
+        let mut fixed = self.fixed.build();
+        fixed.setup(&mut world.res);
+
+        let fixed_seconds = 1.0 / self.tick_rate as f32;
+        world.write_resource::<Time>().set_fixed_seconds(fixed_seconds);
+        world.add_resource(FixedUpdate::default());
+
+        CustomGameData {
+            base,
+            running,
+            fixed,
+            fixed_seconds,
+            max_ticks_per_frame: self.max_ticks_per_frame,
+            accumulator: 0.0,
+        }
     }
 }
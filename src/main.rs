@@ -3,6 +3,7 @@ extern crate serde_derive;
 
 extern crate amethyst;
 extern crate rand;
+extern crate rand_pcg;
 
 use amethyst::core::transform::TransformBundle;
 use amethyst::input::InputBundle;
@@ -13,19 +14,21 @@ use amethyst::renderer::{DisplayConfig, DrawFlat2D, Pipeline, RenderBundle, Stag
 extern crate log;
 extern crate log4rs;
 
+mod collision;
 mod config;
+mod navigation;
 mod sidescroller;
 mod systems;
 mod game_data;
 
-use config::GeneralConfig;
+use config::{ArchetypeConfig, GeneralConfig};
 use game_data::CustomGameDataBuilder;
 
 fn main() -> Result<(), amethyst::Error> {
     // amethyst::start_logger(Default::default());
     log4rs::init_file("log4rs.yml", Default::default()).unwrap();
 
-    use crate::sidescroller::Sidescroller;
+    use crate::sidescroller::{GameRng, Sidescroller};
 
     let binding_path = format!(
         "{}/resources/bindings_config.ron",
@@ -42,9 +45,22 @@ fn main() -> Result<(), amethyst::Error> {
         env!("CARGO_MANIFEST_DIR")
     );
 
+    let archetype_path = format!(
+        "{}/resources/enemy_archetypes.ron",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
     let asset_path = format!("{}", env!("CARGO_MANIFEST_DIR"));
 
     let general_config = GeneralConfig::load(&general_path);
+    let start_level = general_config.map.start_level.clone();
+
+    let seed = seed_from_args()
+        .or(general_config.simulation.seed)
+        .unwrap_or_else(rand::random);
+    info!(target: "game_engine", "Random seed: {}", seed);
+
+    let archetype_config = ArchetypeConfig::load(&archetype_path);
 
     let display_config = DisplayConfig::load(&path);
 
@@ -58,10 +74,34 @@ fn main() -> Result<(), amethyst::Error> {
     );
 
     let game_data = CustomGameDataBuilder::default()
-        .with_running(systems::PlayerSystem, "player_system", &[])
-        .with_running(systems::ActorSystem, "actor_system", &["player_system"])
-        .with_running(systems::EnemySystem, "enemy_system", &["actor_system"])
-        .with_running(systems::GravitySystem, "gravity_system", &["actor_system"])
+        .with_tick_rate(
+            general_config.simulation.tick_rate,
+            general_config.simulation.max_ticks_per_frame,
+        )
+        .with_fixed(systems::PlayerSystem, "player_system", &[])
+        .with_fixed(systems::PlatformSystem, "platform_system", &[])
+        .with_fixed(systems::EnemySystem, "enemy_system", &[])
+        .with_fixed(
+            systems::MovementSystem,
+            "movement_system",
+            &["player_system", "platform_system", "enemy_system"],
+        )
+        .with_fixed(
+            systems::GravitySystem,
+            "gravity_system",
+            &["player_system", "platform_system", "enemy_system"],
+        )
+        .with_fixed(systems::ActorSystem, "actor_system", &["movement_system", "gravity_system"])
+        .with_fixed(systems::ExitSystem, "exit_system", &["actor_system"])
+        .with_fixed(systems::BroadphaseSystem, "broadphase_system", &["actor_system"])
+        .with_fixed(
+            systems::DamageSystem::default(),
+            "damage_system",
+            &["actor_system", "broadphase_system"],
+        )
+        .with_fixed(systems::HealthSystem::default(), "health_system", &["damage_system"])
+        .with_fixed(systems::SpawnerSystem, "spawner_system", &["health_system"])
+        .with_running(systems::CameraSystem, "camera_system", &[])
         .with_base_bundle(TransformBundle::new())?
         .with_base_bundle(
             RenderBundle::new(pipe, Some(display_config))
@@ -69,9 +109,25 @@ fn main() -> Result<(), amethyst::Error> {
                 .with_sprite_visibility_sorting(&["transform_system"]),
         )?.with_base_bundle(input_bundle)?;
         
-    let mut game = Application::build(asset_path, Sidescroller)?
+    let mut game = Application::build(asset_path, Sidescroller::new(start_level))?
         .with_resource(general_config.map)
+        .with_resource(archetype_config)
+        .with_resource(GameRng::new(seed))
         .build(game_data)?;
     game.run();
     Ok(())
 }
+
+/// Seed passed on the command line as `--seed <number>`.
+fn seed_from_args() -> Option<u64> {
+    let args: Vec<String> = std::env::args().collect();
+    let position = args.iter().position(|arg| arg == "--seed")?;
+
+    match args.get(position + 1).map(|value| value.parse()) {
+        Some(Ok(seed)) => Some(seed),
+        _ => {
+            warn!(target: "game_engine", "Ignoring --seed without a valid number");
+            None
+        }
+    }
+}
@@ -0,0 +1,565 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::sidescroller::{Actor, GameMap, MapLayer, TileAttributes};
+use crate::systems::GRAVITY;
+
+/// Cost of moving one tile, links are weighted in tenths of a tile.
+const TILE_COST: u32 = 10;
+
+/// Extra cost of a jump, so walking is preferred when it gets there as well.
+const JUMP_PENALTY: u32 = 20;
+
+/// Tile coordinates of the cell an actor stands in.
+pub type NavNode = (usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NavLinkKind {
+	/// Walk to a neighbouring tile on the same surface.
+	WALK,
+	/// Jump up or across a gap.
+	JUMP,
+	/// Walk off a ledge and drop down.
+	FALL,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavLink {
+	pub target: NavNode,
+	pub kind: NavLinkKind,
+	pub cost: u32,
+}
+
+/// Movement abilities the graph is built for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavSettings {
+	/// Upward jump velocity, negative like `JumpSettings::velocity`.
+	pub jump_velocity: f32,
+	pub gravity: f32,
+	pub run_speed: f32,
+	pub height: f32,
+}
+
+impl NavSettings {
+	/// Jumps and runs the way the actor does.
+	pub fn for_actor(actor: &Actor) -> NavSettings {
+		NavSettings {
+			jump_velocity: actor.jump.velocity,
+			gravity: GRAVITY,
+			run_speed: actor.max_speed,
+			height: actor.height,
+		}
+	}
+
+	/// Highest rise of a jump in whole tiles.
+	fn jump_height(&self, tile_size: f32) -> usize {
+		let height = self.jump_velocity * self.jump_velocity / (2.0 * self.gravity);
+
+		(height / tile_size).floor() as usize
+	}
+
+	/// Farthest horizontal distance of a jump in whole tiles, landing at the height it started from.
+	fn jump_reach(&self, tile_size: f32) -> usize {
+		let air_time = 2.0 * self.jump_velocity.abs() / self.gravity;
+
+		(self.run_speed * air_time / tile_size).floor() as usize
+	}
+
+	/// Tiles of headroom the actor needs.
+	fn clearance(&self, tile_size: f32) -> usize {
+		((self.height / tile_size).ceil() as usize).max(1)
+	}
+}
+
+/// Walkable surfaces of the collision layer and the walk, jump and fall links
+/// between them. Rebuilt whenever a level is loaded.
+#[derive(Debug, Clone, Default)]
+pub struct NavGraph {
+	pub width: usize,
+	pub height: usize,
+	pub tile_size: usize,
+	pub links: HashMap<NavNode, Vec<NavLink>>,
+}
+
+impl NavGraph {
+	/// Graph of the collision layer of the map, empty when it has none.
+	pub fn new(game_map: &GameMap, settings: &NavSettings) -> NavGraph {
+		match game_map.collision_layer() {
+			Some(layer) => NavGraph::from_layer(
+				layer,
+				game_map.tile_size,
+				|gid| game_map.get_tile_attributes(gid),
+				settings,
+			),
+			None => NavGraph {
+				width: game_map.width,
+				height: game_map.height,
+				tile_size: game_map.tile_size,
+				links: HashMap::new(),
+			},
+		}
+	}
+
+	/// Builds the graph from a collision layer, looking up the attributes of its tile gids.
+	pub fn from_layer<F>(layer: &MapLayer, tile_size: usize, attributes: F, settings: &NavSettings) -> NavGraph
+	where
+		F: Fn(u32) -> TileAttributes,
+	{
+		let height = layer.tiles.len();
+		let width = layer.tiles.iter().map(|row| row.len()).max().unwrap_or(0);
+
+		let grid = Grid {
+			width: width,
+			height: height,
+			clearance: settings.clearance(tile_size as f32),
+			tile: |x: usize, y: usize| match layer.tiles[y].get(x) {
+				Some(gid) => attributes(*gid),
+				None => TileAttributes::default(),
+			},
+		};
+
+		let jump_height = settings.jump_height(tile_size as f32);
+		let jump_reach = settings.jump_reach(tile_size as f32);
+
+		let mut links = HashMap::new();
+
+		for y in 0..height {
+			for x in 0..width {
+				if grid.walkable(x, y) {
+					links.insert((x, y), grid.links_from((x, y), jump_height, jump_reach));
+				}
+			}
+		}
+
+		NavGraph {
+			width: width,
+			height: height,
+			tile_size: tile_size,
+			links: links,
+		}
+	}
+
+	pub fn is_node(&self, node: NavNode) -> bool {
+		self.links.contains_key(&node)
+	}
+
+	/// Node of the surface below a world position, for actors that are in the air or slightly off the grid.
+	pub fn nearest_node(&self, x: f32, y: f32) -> Option<NavNode> {
+		if x < 0.0 || y < 0.0 || self.tile_size == 0 {
+			return None;
+		}
+
+		let tile_x = (x / self.tile_size as f32) as usize;
+		let tile_y = (y / self.tile_size as f32) as usize;
+
+		(tile_y..self.height)
+			.map(|below| (tile_x, below))
+			.find(|node| self.is_node(*node))
+	}
+
+	/// Cheapest chain of links from one node to another with A*.
+	pub fn find_path(&self, start: NavNode, goal: NavNode) -> Option<Vec<NavLink>> {
+		if !self.is_node(start) || !self.is_node(goal) {
+			return None;
+		}
+
+		let mut open = BinaryHeap::new();
+		let mut costs: HashMap<NavNode, u32> = HashMap::new();
+		let mut came_from: HashMap<NavNode, (NavNode, NavLink)> = HashMap::new();
+
+		costs.insert(start, 0);
+		open.push(OpenNode {
+			node: start,
+			estimate: heuristic(start, goal),
+		});
+
+		while let Some(OpenNode { node, .. }) = open.pop() {
+			if node == goal {
+				let mut path = Vec::new();
+				let mut current = goal;
+
+				while let Some((previous, link)) = came_from.get(&current) {
+					path.push(*link);
+					current = *previous;
+				}
+
+				path.reverse();
+				return Some(path);
+			}
+
+			let cost = costs[&node];
+
+			for link in &self.links[&node] {
+				let next_cost = cost + link.cost;
+
+				if costs.get(&link.target).map_or(true, |known| next_cost < *known) {
+					costs.insert(link.target, next_cost);
+					came_from.insert(link.target, (node, *link));
+					open.push(OpenNode {
+						node: link.target,
+						estimate: next_cost + heuristic(link.target, goal),
+					});
+				}
+			}
+		}
+
+		None
+	}
+}
+
+/// Navigation graphs of the current level, one for each movement profile in use.
+/// A graph is built the first time an actor with its profile asks for it, and
+/// they are all dropped when a level is loaded.
+#[derive(Debug, Default)]
+pub struct NavGraphs {
+	graphs: Vec<(NavSettings, NavGraph)>,
+}
+
+impl NavGraphs {
+	pub fn get(&mut self, game_map: &GameMap, settings: &NavSettings) -> &NavGraph {
+		let index = match self.graphs.iter().position(|(known, _)| known == settings) {
+			Some(index) => index,
+			None => {
+				let graph = NavGraph::new(game_map, settings);
+				debug!(target: "game_engine", "Navigation graph for {:?} has {} nodes", settings, graph.links.len());
+
+				self.graphs.push((settings.clone(), graph));
+				self.graphs.len() - 1
+			}
+		};
+
+		&self.graphs[index].1
+	}
+}
+
+/// Tile queries used while building the graph.
+struct Grid<F> {
+	width: usize,
+	height: usize,
+	clearance: usize,
+	tile: F,
+}
+
+impl<F> Grid<F>
+where
+	F: Fn(usize, usize) -> TileAttributes,
+{
+	/// An actor can be inside the tile. Tiles outside the map are blocked.
+	fn passable(&self, x: usize, y: usize) -> bool {
+		if x >= self.width || y >= self.height {
+			return false;
+		}
+
+		let attributes = (self.tile)(x, y);
+		!attributes.solid || attributes.slope.is_some()
+	}
+
+	/// The actor fits in the tile with its head room above.
+	fn open(&self, x: usize, y: usize) -> bool {
+		(0..self.clearance).all(|up| y >= up && self.passable(x, y - up))
+	}
+
+	/// An actor can stand in the tile, on top of the tile below or on a slope.
+	fn walkable(&self, x: usize, y: usize) -> bool {
+		if !self.open(x, y) {
+			return false;
+		}
+
+		let attributes = (self.tile)(x, y);
+
+		if attributes.hazard {
+			return false;
+		}
+
+		if attributes.slope.is_some() {
+			return true;
+		}
+
+		if y + 1 >= self.height {
+			return false;
+		}
+
+		let below = (self.tile)(x, y + 1);
+		!below.hazard && ((below.solid && below.slope.is_none()) || below.one_way)
+	}
+
+	/// Every tile in the row between the two columns is open.
+	fn clear_row(&self, y: usize, from_x: usize, to_x: usize) -> bool {
+		(from_x.min(to_x)..=from_x.max(to_x)).all(|x| self.open(x, y))
+	}
+
+	/// Every tile in the column between the two rows is open.
+	fn clear_column(&self, x: usize, from_y: usize, to_y: usize) -> bool {
+		(from_y.min(to_y)..=from_y.max(to_y)).all(|y| self.open(x, y))
+	}
+
+	fn links_from(&self, node: NavNode, jump_height: usize, jump_reach: usize) -> Vec<NavLink> {
+		let (x, y) = node;
+		let mut links = Vec::new();
+
+		for &step in &[-1i32, 1] {
+			let next_x = x as i32 + step;
+
+			if next_x < 0 || next_x as usize >= self.width {
+				continue;
+			}
+
+			let next_x = next_x as usize;
+
+			// Along the surface, slopes go up or down a tile at a time
+			let walk = [y.checked_sub(1), Some(y), Some(y + 1)]
+				.iter()
+				.filter_map(|next_y| *next_y)
+				.find(|next_y| {
+					self.walkable(next_x, *next_y)
+						&& (*next_y == y
+							|| (self.tile)(x, y).slope.is_some()
+							|| (self.tile)(next_x, *next_y).slope.is_some())
+				});
+
+			if let Some(next_y) = walk {
+				links.push(NavLink {
+					target: (next_x, next_y),
+					kind: NavLinkKind::WALK,
+					cost: if next_y == y { TILE_COST } else { TILE_COST * 14 / 10 },
+				});
+				continue;
+			}
+
+			// Off the ledge and straight down to the first surface
+			if self.open(next_x, y) {
+				let landing = (y + 1..self.height)
+					.take_while(|below| self.passable(next_x, *below))
+					.find(|below| self.walkable(next_x, *below));
+
+				if let Some(landing) = landing {
+					links.push(NavLink {
+						target: (next_x, landing),
+						kind: NavLinkKind::FALL,
+						cost: TILE_COST * (1 + landing - y) as u32,
+					});
+				}
+			}
+		}
+
+		links.extend(self.jumps_from(node, jump_height, jump_reach));
+		links
+	}
+
+	/// Jumps up onto higher surfaces and across gaps. The arc is approximated by
+	/// rising straight up, moving across and dropping down, which all has to be clear.
+	fn jumps_from(&self, node: NavNode, jump_height: usize, jump_reach: usize) -> Vec<NavLink> {
+		let (x, y) = node;
+		let mut jumps = Vec::new();
+
+		if jump_height == 0 && jump_reach < 2 {
+			return jumps;
+		}
+
+		let min_x = x.saturating_sub(jump_reach);
+		let max_x = (x + jump_reach).min(self.width - 1);
+		let min_y = y.saturating_sub(jump_height);
+
+		for target_y in min_y..self.height {
+			for target_x in min_x..=max_x {
+				let dx = (target_x as i32 - x as i32).abs() as usize;
+
+				// Neighbours on the same level or below are reached by walking or falling
+				if target_y >= y && dx < 2 {
+					continue;
+				}
+
+				if (target_x, target_y) == node || !self.walkable(target_x, target_y) {
+					continue;
+				}
+
+				let clear = if target_y <= y {
+					self.clear_column(x, target_y, y) && self.clear_row(target_y, x, target_x)
+				} else {
+					self.clear_row(y, x, target_x) && self.clear_column(target_x, y, target_y)
+				};
+
+				if !clear {
+					continue;
+				}
+
+				let dy = (target_y as i32 - y as i32).abs() as usize;
+
+				jumps.push(NavLink {
+					target: (target_x, target_y),
+					kind: NavLinkKind::JUMP,
+					cost: TILE_COST * (dx + dy) as u32 + JUMP_PENALTY,
+				});
+			}
+		}
+
+		jumps
+	}
+}
+
+/// Never more than the real cost, since every link costs at least a tile per tile travelled.
+fn heuristic(from: NavNode, to: NavNode) -> u32 {
+	let dx = (from.0 as i32 - to.0 as i32).abs();
+	let dy = (from.1 as i32 - to.1 as i32).abs();
+
+	TILE_COST * dx.max(dy) as u32
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct OpenNode {
+	node: NavNode,
+	estimate: u32,
+}
+
+// Reversed so the `BinaryHeap` pops the lowest estimate first
+impl Ord for OpenNode {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other
+			.estimate
+			.cmp(&self.estimate)
+			.then_with(|| self.node.cmp(&other.node))
+	}
+}
+
+impl PartialOrd for OpenNode {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::collections::HashMap;
+
+	const TILE_SIZE: usize = 32;
+
+	/// Jumps two tiles high and two tiles across.
+	fn settings() -> NavSettings {
+		NavSettings {
+			jump_velocity: -400.0,
+			gravity: 1000.0,
+			run_speed: 80.0,
+			height: 32.0,
+		}
+	}
+
+	/// Collision layer drawn with `#` for solid tiles, `-` for one-way tiles and `.` for empty ones.
+	fn layer(rows: &[&str]) -> MapLayer {
+		let tiles = rows
+			.iter()
+			.map(|row| {
+				row.chars()
+					.map(|tile| match tile {
+						'#' => 1,
+						'-' => 2,
+						_ => 0,
+					})
+					.collect()
+			})
+			.collect();
+
+		MapLayer {
+			name: String::from("collision"),
+			properties: HashMap::new(),
+			tiles: tiles,
+		}
+	}
+
+	fn attributes(gid: u32) -> TileAttributes {
+		TileAttributes {
+			solid: gid == 1,
+			one_way: gid == 2,
+			..TileAttributes::default()
+		}
+	}
+
+	fn graph(rows: &[&str]) -> NavGraph {
+		NavGraph::from_layer(&layer(rows), TILE_SIZE, attributes, &settings())
+	}
+
+	fn has_link(graph: &NavGraph, from: NavNode, to: NavNode, kind: NavLinkKind) -> bool {
+		graph.links[&from].iter().any(|link| link.target == to && link.kind == kind)
+	}
+
+	#[test]
+	fn walk_links_on_a_flat_floor() {
+		let graph = graph(&[
+			".....",
+			".....",
+			"#####",
+		]);
+
+		for x in 0..5 {
+			assert!(graph.is_node((x, 1)));
+			assert!(!graph.is_node((x, 0)));
+		}
+
+		assert!(has_link(&graph, (1, 1), (0, 1), NavLinkKind::WALK));
+		assert!(has_link(&graph, (1, 1), (2, 1), NavLinkKind::WALK));
+	}
+
+	#[test]
+	fn fall_link_off_a_ledge() {
+		let graph = graph(&[
+			"......",
+			"###...",
+			"......",
+			"......",
+			"######",
+		]);
+
+		assert!(has_link(&graph, (2, 0), (3, 3), NavLinkKind::FALL));
+	}
+
+	#[test]
+	fn jump_links_only_within_jump_height() {
+		let graph = graph(&[
+			"........",
+			"......#.",
+			"........",
+			"..##....",
+			"........",
+			"########",
+		]);
+
+		// Two tiles up onto the low platform
+		assert!(has_link(&graph, (1, 4), (2, 2), NavLinkKind::JUMP));
+
+		// The high platform is four tiles above the floor and too far from the low one
+		assert!(graph.is_node((6, 0)));
+		assert!(graph
+			.links
+			.values()
+			.flatten()
+			.all(|link| link.target != (6, 0)));
+	}
+
+	#[test]
+	fn path_across_a_gap() {
+		let graph = graph(&[
+			".......",
+			"##.####",
+		]);
+
+		let path = graph.find_path((0, 0), (6, 0)).unwrap();
+
+		assert!(path.iter().any(|link| link.kind == NavLinkKind::JUMP));
+		assert_eq!(path.last().unwrap().target, (6, 0));
+	}
+
+	#[test]
+	fn no_path_over_a_wall_too_high_to_jump() {
+		let graph = graph(&[
+			"...#...",
+			"...#...",
+			"...#...",
+			"#######",
+		]);
+
+		assert!(graph.is_node((0, 2)));
+		assert!(graph.is_node((6, 2)));
+		assert_eq!(graph.find_path((0, 2), (6, 2)), None);
+	}
+}
@@ -1,31 +1,49 @@
 extern crate tiled;
 
+use crate::collision::{Contacts, Rect};
+use crate::config::{ArchetypeConfig, EnemyArchetype};
 use crate::game_data::CustomGameData;
+use crate::navigation::{NavGraphs, NavLink};
 
 use amethyst::assets::{AssetStorage, Loader};
 use amethyst::core::nalgebra::Vector3;
-use amethyst::core::transform::{GlobalTransform, Transform};
+use amethyst::core::transform::{GlobalTransform, Parent, Transform};
 use amethyst::input::{is_close_requested, is_key_down};
 use amethyst::prelude::*;
 use amethyst::renderer::{
 	Camera, PngFormat, Projection, Sprite, SpriteRender, SpriteSheet,
-	SpriteSheetHandle, Texture, TextureCoordinates, VirtualKeyCode, TextureMetadata,
+	SpriteSheetHandle, Texture, VirtualKeyCode, TextureMetadata,
 	SpriteSheetFormat
 };
-use amethyst::ecs::prelude::{Component, DenseVecStorage};
+use amethyst::ecs::prelude::{Component, DenseVecStorage, Entity, Join, NullStorage};
 
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
 
-use self::tiled::parse;
+use self::tiled::{parse, ObjectShape, Properties, PropertyValue, Tile, Tileset};
+
+pub struct Sidescroller {
+	level: String,
+}
+
+impl Sidescroller {
+	pub fn new(level: String) -> Sidescroller {
+		Sidescroller { level: level }
+	}
+}
 
-pub struct Sidescroller;
 pub struct Menu;
 
 pub const CAMERA_WIDTH: f32 = 800.0;
 pub const CAMERA_HEIGHT: f32 = 600.0;
 
+pub const PLAYER_HEALTH: u32 = 3;
+
 use crate::config::MapConfig;
 
 impl<'a, 'b> State<CustomGameData<'a, 'b>, StateEvent> for Menu {
@@ -62,18 +80,13 @@ impl<'a, 'b> State<CustomGameData<'a, 'b>, StateEvent> for Sidescroller {
 	fn on_start(&mut self, data: StateData<CustomGameData>) {
 		let world = data.world;
 
-		initialise_camera(world);
-
-		let sprite_sheet_handle = load_sprite_sheet(world);
-		let enemy_sprite_sheet_handle = load_enemy_sprite_sheet(world);
-
-		initialise_map(world);
-
 		world.register::<Player>();
 		world.register::<Actor>();
+		world.register::<LevelEntity>();
 
-		initialise_player(world, sprite_sheet_handle);
-		initialise_actor(world, enemy_sprite_sheet_handle);
+		initialise_camera(world);
+
+		load_level(world, &self.level, None);
 	}
 
 	fn handle_event(
@@ -96,43 +109,62 @@ impl<'a, 'b> State<CustomGameData<'a, 'b>, StateEvent> for Sidescroller {
 
 	fn update(&mut self, data: StateData<CustomGameData>) -> Trans<CustomGameData<'a, 'b>, StateEvent> {
 		data.data.update(&data.world, true);
+
+		let transition = data.world.write_resource::<LevelTransition>().target.take();
+
+		if let Some((level, spawn)) = transition {
+			debug!(target: "game_engine", "Level transition to {:?} at spawn {:?}", level, spawn);
+
+			// Stay in the current level rather than unloading it for one that can't be loaded
+			if data.world.read_resource::<MapConfig>().get_level(&level).is_none() {
+				error!(target: "game_engine", "Couldn't find level {:?} in the map config", level);
+				return Trans::None;
+			}
+
+			// Enemies from spawners are built lazily and only get their `LevelEntity` on maintain
+			data.world.maintain();
+
+			unload_level(data.world);
+			self.level = level;
+			load_level(data.world, &self.level, spawn.as_ref().map(|spawn| spawn.as_str()));
+		}
+
 		Trans::None
 	}
 }
 
-fn load_sprite_sheet(world: &mut World) -> SpriteSheetHandle {
-	let texture_handle = {
-		let loader = world.read_resource::<Loader>();
-		let texture_storage = world.read_resource::<AssetStorage<Texture>>();
-		loader.load(
-			"sprites/player.png",
-			PngFormat,
-			TextureMetadata::srgb_scale(),
-			(),
-			&texture_storage,
-		)
-	};
+fn load_level(world: &mut World, level: &str, spawn: Option<&str>) {
+	let sprite_sheet_handle = load_sprite_sheet(world, "sprites/player.png", "sprites/player_spritesheet.ron");
 
-	let loader = world.read_resource::<Loader>();
-	let sprite_sheet_store = world.read_resource::<AssetStorage<SpriteSheet>>();
+	initialise_map(world, level);
 
-	loader.load(
-		"sprites/player_spritesheet.ron",
-		SpriteSheetFormat,
-		texture_handle, 
-		(),
-		&sprite_sheet_store,
-	)
+	initialise_player(world, sprite_sheet_handle, spawn);
+	initialise_actor(world);
+	initialise_spawners(world);
+}
+
+fn unload_level(world: &mut World) {
+	let level_entities: Vec<Entity> = {
+		let entities = world.entities();
+		let level_storage = world.read_storage::<LevelEntity>();
+
+		(&*entities, &level_storage)
+			.join()
+			.map(|(entity, _)| entity)
+			.collect()
+	};
 
+	if let Err(err) = world.delete_entities(&level_entities) {
+		error!(target: "game_engine", "Couldn't delete level entities: {:?}", err);
+	}
 }
 
-// TODO: Better way to handle multiple spritesheet loading
-fn load_enemy_sprite_sheet(world: &mut World) -> SpriteSheetHandle {
+fn load_sprite_sheet(world: &mut World, texture_path: &str, sprite_sheet_path: &str) -> SpriteSheetHandle {
 	let texture_handle = {
 		let loader = world.read_resource::<Loader>();
 		let texture_storage = world.read_resource::<AssetStorage<Texture>>();
 		loader.load(
-			"sprites/enemy.png",
+			texture_path,
 			PngFormat,
 			TextureMetadata::srgb_scale(),
 			(),
@@ -144,7 +176,7 @@ fn load_enemy_sprite_sheet(world: &mut World) -> SpriteSheetHandle {
 	let sprite_sheet_store = world.read_resource::<AssetStorage<SpriteSheet>>();
 
 	loader.load(
-		"sprites/enemy_spritesheet.ron",
+		sprite_sheet_path,
 		SpriteSheetFormat,
 		texture_handle, 
 		(),
@@ -171,12 +203,22 @@ fn initialise_camera(world: &mut World) {
 		.build();
 }
 
-fn initialise_player(world: &mut World, sprite_sheet_handle: SpriteSheetHandle) {
+fn initialise_player(world: &mut World, sprite_sheet_handle: SpriteSheetHandle, spawn: Option<&str>) {
 	let game_map = world.read_resource::<GameMap>().clone();
-	let player_data = game_map.get_player();
+
+	let spawn_position = match spawn.and_then(|name| game_map.get_spawn_point(name)) {
+		Some(position) => position,
+		None => match game_map.get_player() {
+			Some(player) => player.spawn,
+			None => {
+				error!(target: "game_engine", "Level {:?} has no player object or spawn point", game_map.level);
+				(0.0, 0.0)
+			}
+		},
+	};
 
 	let mut player_transform = Transform::default();
-	player_transform.set_xyz(player_data.spawn.0, player_data.spawn.1, 0.1);
+	player_transform.set_xyz(spawn_position.0, spawn_position.1, 0.1);
 
 	let sprite_render = SpriteRender {
 		sprite_sheet: sprite_sheet_handle.clone(),
@@ -186,51 +228,130 @@ fn initialise_player(world: &mut World, sprite_sheet_handle: SpriteSheetHandle)
 	world
 		.create_entity()
 		.with(sprite_render)
-		.with(Actor::new(32.0, 300.0, ActorType::PLAYER))
+		.with(Actor::new(spawn_position.0, spawn_position.1, ActorType::PLAYER))
 		.with(Player::new())
+		.with(Health::new(PLAYER_HEALTH))
+		.with(LevelEntity)
 		.with(GlobalTransform::default())
 		.with(player_transform)
 		.build();
 }
 
-fn initialise_actor(world: &mut World, sprite_sheet_handle: SpriteSheetHandle) {
+fn initialise_actor(world: &mut World) {
 	let game_map = world.read_resource::<GameMap>().clone();
+	let archetypes = world.read_resource::<ArchetypeConfig>().clone();
+
+	// Archetypes sharing a sprite sheet share its handle
+	let mut sprite_sheets: HashMap<String, SpriteSheetHandle> = HashMap::new();
+
+	for map_actor in &game_map.actors {
+		let actor = &map_actor.actor;
+
+		if actor.actor_type == ActorType::ENEMY {
+			debug!(target: "game_engine", "Spawn actor: {:?}", actor);
+
+			let archetype = get_enemy_archetype(&archetypes, &map_actor.properties);
+
+			let sprite_sheet_handle = sprite_sheets
+				.entry(archetype.sprite_sheet.clone())
+				.or_insert_with(|| load_sprite_sheet(world, &archetype.texture, &archetype.sprite_sheet))
+				.clone();
+
+			build_enemy(
+				world.create_entity(),
+				&archetype,
+				&map_actor.properties,
+				actor.spawn,
+				sprite_sheet_handle,
+			);
+		}
+	}
+}
+
+/// Adds the components of an enemy to an entity builder. Enemies placed in Tiled
+/// are built with `world.create_entity()` and the ones from spawners with `LazyUpdate`.
+pub fn build_enemy<B: Builder>(
+	builder: B,
+	archetype: &EnemyArchetype,
+	properties: &Properties,
+	position: (f32, f32),
+	sprite_sheet_handle: SpriteSheetHandle,
+) -> Entity {
+	let mut actor_transform = Transform::default();
+	actor_transform.set_xyz(position.0, position.1, 0.1);
 
 	let sprite_render = SpriteRender {
-		sprite_sheet: sprite_sheet_handle.clone(),
+		sprite_sheet: sprite_sheet_handle,
 		sprite_number: 0
 	};
 
-	for actor in &game_map.actors {
-		if actor.actor_type == ActorType::ENEMY {
-			let mut actor_transform = Transform::default();
-			actor_transform.set_xyz(actor.spawn.0, actor.spawn.1, 0.1);
+	let enemy = Enemy::new(archetype, properties);
 
-			debug!(target: "game_engine", "Spawn actor: {:?}", actor);
+	let mut actor = Actor::new(position.0, position.1, ActorType::ENEMY);
+	actor.width = archetype.width;
+	actor.height = archetype.height;
+	actor.max_speed = enemy.speed;
+	actor.jump.velocity = archetype.jump_velocity;
 
-			world
-				.create_entity()
-				.with(sprite_render.clone())
-				.with(actor.clone())
-				.with(Enemy::new())
-				.with(GlobalTransform::default())
-				.with(actor_transform)
-				.build();
+	builder
+		.with(sprite_render)
+		.with(actor)
+		.with(enemy)
+		.with(Health::new(archetype.health))
+		.with(LevelEntity)
+		.with(GlobalTransform::default())
+		.with(actor_transform)
+		.build()
+}
+
+fn initialise_spawners(world: &mut World) {
+	let game_map = world.read_resource::<GameMap>().clone();
+	let archetypes = world.read_resource::<ArchetypeConfig>().clone();
+
+	let mut sprite_sheets: HashMap<String, SpriteSheetHandle> = HashMap::new();
+
+	for map_spawner in &game_map.spawners {
+		let archetype = get_enemy_archetype(&archetypes, &map_spawner.properties);
+
+		let sprite_sheet_handle = sprite_sheets
+			.entry(archetype.sprite_sheet.clone())
+			.or_insert_with(|| load_sprite_sheet(world, &archetype.texture, &archetype.sprite_sheet))
+			.clone();
+
+		let mut spawner_transform = Transform::default();
+		spawner_transform.set_xyz(map_spawner.x, map_spawner.y, 0.0);
+
+		world
+			.create_entity()
+			.with(Spawner::new(map_spawner, archetype, sprite_sheet_handle))
+			.with(LevelEntity)
+			.with(spawner_transform)
+			.build();
+	}
+}
+
+/// Archetype named by the `archetype` property of an enemy object, the default one when it has none.
+fn get_enemy_archetype(archetypes: &ArchetypeConfig, properties: &Properties) -> EnemyArchetype {
+	let name = get_string_property(properties, "archetype").unwrap_or_else(|| String::from("default"));
+
+	match archetypes.get_archetype(&name) {
+		Some(archetype) => archetype.clone(),
+		None => {
+			warn!(target: "game_engine", "Unknown enemy archetype {:?}, using the default one", name);
+			archetypes.get_archetype("default").cloned().unwrap_or_default()
 		}
 	}
 }
 
-fn load_tileset_sheet(
-	world: &mut World,
-	tileset_path: &String,
-	tile_size: f32,
-	tile_sheet_width: f32,
-) -> SpriteSheetHandle {
+fn load_tileset_sheet(world: &mut World, tileset: &Tileset, path_to_maps: &PathBuf) -> SpriteSheetHandle {
+	let image = tileset.images.get(0).unwrap();
+	let tileset_path = path_to_maps.join(&image.source);
+
 	let texture_handle = {
 		let loader = world.read_resource::<Loader>();
 		let texture_storage = world.read_resource::<AssetStorage<Texture>>();
 		loader.load(
-			tileset_path.clone().as_str(),
+			tileset_path.into_os_string().into_string().unwrap().as_str(),
 			PngFormat,
 			TextureMetadata::srgb_scale(),
 			(),
@@ -238,122 +359,170 @@ fn load_tileset_sheet(
 		)
 	};
 
-	// TODO: Add support for rows in the tile sheet
-	let width_ratio = tile_size / tile_sheet_width;
-	let tile_amount = tile_sheet_width / tile_size;
+	let image_width = image.width as u32;
+	let image_height = image.height as u32;
 
-	let mut sprite_vec = Vec::new();
+	// The tiled crate doesn't expose columns or tilecount, so compute them the
+	// same way Tiled does from the image size, margin and spacing
+	let columns = (image_width - 2 * tileset.margin + tileset.spacing)
+		/ (tileset.tile_width + tileset.spacing);
+	let rows = (image_height - 2 * tileset.margin + tileset.spacing)
+		/ (tileset.tile_height + tileset.spacing);
 
-	debug!(target: "game_engine", "width_ratio: {:?}", width_ratio);
-	debug!(target: "game_engine", "tile_size: {:?}", tile_size);
-	debug!(target: "game_engine", "tile_sheet_width: {:?}", tile_sheet_width);
+	debug!(target: "game_engine", "Tileset {:?}: {} columns, {} rows", tileset.name, columns, rows);
 
-	for i in 0..tile_amount as i32 {
-		let tile_coords = TextureCoordinates {
-			left: (i as f32) * width_ratio,
-			right: (i as f32) * width_ratio + width_ratio,
-			bottom: 0.0,
-			top: 0.5,
-		};
-		debug!(target: "game_engine", "tile: {:?}", tile_coords);
-		let tile_sprite = Sprite {
-			width: tile_size,
-			height: tile_size,
-			offsets: [tile_size / 2.0, tile_size / 2.0],
-			tex_coords: tile_coords,
-		};
+	let mut sprite_vec = Vec::new();
 
-		sprite_vec.push(tile_sprite.clone());
+	for row in 0..rows {
+		for column in 0..columns {
+			let tile_sprite = Sprite::from_pixel_values(
+				image_width,
+				image_height,
+				tileset.tile_width,
+				tileset.tile_height,
+				tileset.margin + column * (tileset.tile_width + tileset.spacing),
+				tileset.margin + row * (tileset.tile_height + tileset.spacing),
+				[0.0, 0.0],
+			);
+
+			sprite_vec.push(tile_sprite);
+		}
 	}
 
-
 	let loader = world.read_resource::<Loader>();
 	let sprite_sheet_store = world.read_resource::<AssetStorage<SpriteSheet>>();
 
-	loader.load(
-		"resources/maps/tilesets/map_textures.ron",
-		SpriteSheetFormat,
-		texture_handle, 
+	loader.load_from_data(
+		SpriteSheet {
+			texture: texture_handle,
+			sprites: sprite_vec,
+		},
 		(),
 		&sprite_sheet_store,
 	)
+}
 
+struct TilesetSheet {
+	first_gid: u32,
+	handle: SpriteSheetHandle,
 }
 
-fn initialise_map(world: &mut World) {
-	let path_to_maps = PathBuf::from(&world.read_resource::<MapConfig>().map_path);
+/// Resolves a global tile id to the sprite sheet of its tileset and the local
+/// index in that sheet. The sheets have to be sorted by `first_gid`.
+fn get_tile_sprite(tileset_sheets: &[TilesetSheet], gid: u32) -> Option<SpriteRender> {
+	if gid == 0 {
+		return None;
+	}
+
+	tileset_sheets
+		.iter()
+		.rev()
+		.find(|sheet| sheet.first_gid <= gid)
+		.map(|sheet| SpriteRender {
+			sprite_sheet: sheet.handle.clone(),
+			sprite_number: (gid - sheet.first_gid) as usize,
+		})
+}
+
+fn initialise_map(world: &mut World, level: &str) {
+	let map_config = world.read_resource::<MapConfig>().clone();
+	let path_to_maps = PathBuf::from(&map_config.map_path);
 
 	debug!(target: "game_engine", "Maps folder: {:?}", path_to_maps);
 
-	let map_file = File::open(path_to_maps.join("test1.tmx")).unwrap();
+	let level_config = match map_config.get_level(level) {
+		Some(level_config) => level_config,
+		None => panic!("Couldn't find level {:?} in the map config", level),
+	};
+
+	debug!(target: "game_engine", "Loading level {:?} from {:?}", level, level_config.file);
+
+	let map_file = File::open(path_to_maps.join(&level_config.file)).unwrap();
 	let reader = BufReader::new(map_file);
 	let map = parse(reader).unwrap();
 
 	debug!(target: "game_engine", "{:?}", map);
 
-	let tileset_path = &map
-		.get_tileset_by_gid(1)
-		.unwrap()
-		.images
-		.get(0)
-		.unwrap()
-		.source;
-
-	debug!(target: "game_engine", "Tileset: {:?}", &map.get_tileset_by_gid(1).unwrap());
-
-	let tile_size = &map.get_tileset_by_gid(1).unwrap().tile_width;
-	let tile_sheet_width = &map
-		.get_tileset_by_gid(1)
-		.unwrap()
-		.images
-		.get(0)
-		.unwrap()
-		.width;
-
-	let tileset_path = path_to_maps.join(tileset_path);
-
-	let tileset_sheet_handle = load_tileset_sheet(
-		world,
-		&tileset_path.clone().into_os_string().into_string().unwrap(),
-		tile_size.clone() as f32,
-		tile_sheet_width.clone() as f32,
-	);
+	let mut tileset_sheets = Vec::new();
+
+	for tileset in &map.tilesets {
+		debug!(target: "game_engine", "Tileset: {:?}", tileset);
+
+		if tileset.images.is_empty() {
+			warn!(target: "game_engine", "Tileset {:?} has no image, skipping it", tileset.name);
+			continue;
+		}
+
+		tileset_sheets.push(TilesetSheet {
+			first_gid: tileset.first_gid,
+			handle: load_tileset_sheet(world, tileset, &path_to_maps),
+		});
+	}
+
+	tileset_sheets.sort_by_key(|sheet| sheet.first_gid);
 
 	let map_height = &(map.height as usize);
 	let map_width = &(map.width as usize);
 
-	let mut game_map = GameMap::new(map_width.clone(), map_height.clone());
+	let mut game_map = GameMap::new(level, map_width.clone(), map_height.clone());
+	game_map.tile_size = map.tile_width as usize;
+
+	for tileset in &map.tilesets {
+		for tile in &tileset.tiles {
+			let attributes = TileAttributes::from_properties(&tile.properties);
+
+			debug!(target: "game_engine", "Tile {} attributes: {:?}", tileset.first_gid + tile.id, attributes);
 
-	for layer in 0..2 {
-		let tiles = &map.layers.get(layer).unwrap().tiles;
+			game_map.set_tile_attributes(tileset.first_gid + tile.id, attributes);
 
-		let tile_layer = MapLayer::new(tiles.clone());
+			let hitboxes = get_tile_hitboxes(tile);
+
+			if !hitboxes.is_empty() {
+				debug!(target: "game_engine", "Tile {} hitboxes: {:?}", tileset.first_gid + tile.id, hitboxes);
+
+				game_map.set_tile_hitboxes(tileset.first_gid + tile.id, hitboxes);
+			}
+		}
+	}
+
+	let tile_size = game_map.tile_size as f32;
+
+	for layer in &map.layers {
+		let tile_layer = MapLayer::new(&layer.name, layer.properties.clone(), layer.tiles.clone());
 
 		game_map.push(tile_layer);
+	}
+
+	game_map.find_collision_layer();
+
+	debug!(target: "game_engine", "Collision layer: {:?}", game_map.collision_layer);
+
+	for (layer_index, layer) in map.layers.iter().enumerate() {
+		if !layer.visible {
+			continue;
+		}
+
+		let tiles = &layer.tiles;
+		let depth = game_map.layer_depth(layer_index);
 
 		for row in 0..tiles.len() {
 			for tile in 0..tiles[row].len() {
-				let tile_style = (tiles[row][tile] as i32) - 1;
-
-				if tile_style == -1 {
-					continue;
-				}
+				let tileset_render = match get_tile_sprite(&tileset_sheets, tiles[row][tile]) {
+					Some(tileset_render) => tileset_render,
+					None => continue,
+				};
 
 				let mut tile_transform = Transform::default();
 				tile_transform.set_xyz(
-					32.0 * (tile as f32),
-					32.0 * (row as f32),
-					0.0,
+					tile_size * (tile as f32 + 0.5),
+					tile_size * (row as f32 + 0.5),
+					depth,
 				);
 
-				let tileset_render = SpriteRender {
-					sprite_sheet: tileset_sheet_handle.clone(),
-					sprite_number: (tile_style as usize)
-				};
-
 				world
 					.create_entity()
 					.with(tileset_render)
+					.with(LevelEntity)
 					.with(GlobalTransform::default())
 					.with(tile_transform)
 					.build();
@@ -366,6 +535,74 @@ fn initialise_map(world: &mut World) {
 
 		for object in map_objects {
 
+			if object.obj_type == "exit" {
+				let target_level = match get_string_property(&object.properties, "target") {
+					Some(target_level) => target_level,
+					None => {
+						warn!(target: "game_engine", "Exit {:?} has no target level", object.name);
+						continue;
+					}
+				};
+
+				if map_config.get_level(&target_level).is_none() {
+					warn!(target: "game_engine", "Exit {:?} leads to unknown level {:?}, skipping it", object.name, target_level);
+					continue;
+				}
+
+				let exit = MapExit {
+					x: object.x,
+					y: object.y,
+					width: object.width,
+					height: object.height,
+					target_level: target_level,
+					target_spawn: get_string_property(&object.properties, "spawn"),
+				};
+				debug!(target: "game_engine", "{:?}", exit);
+				game_map.add_exit(exit);
+				continue;
+			} else if object.obj_type == "spawn" {
+				game_map.add_spawn_point(&object.name, (object.x, object.y));
+				continue;
+			} else if object.obj_type == "platform" {
+				let path = match &object.shape {
+					ObjectShape::Polyline { points } if !points.is_empty() => points
+						.iter()
+						.map(|point| (object.x + point.0, object.y + point.1))
+						.collect(),
+					_ => {
+						warn!(target: "game_engine", "Platform {:?} has no polyline path", object.name);
+						continue;
+					}
+				};
+
+				let platform = MapPlatform {
+					path: path,
+					speed: get_float_property(&object.properties, "speed").unwrap_or(64.0),
+					ping_pong: get_string_property(&object.properties, "mode")
+						.map_or(false, |mode| mode == "ping_pong"),
+					tiles: get_float_property(&object.properties, "tiles").unwrap_or(3.0) as usize,
+					gid: get_float_property(&object.properties, "tile").unwrap_or(1.0) as u32,
+				};
+				debug!(target: "game_engine", "{:?}", platform);
+				game_map.add_platform(platform);
+				continue;
+			} else if object.obj_type == "spawner" {
+				let spawner = MapSpawner {
+					x: object.x + object.width / 2.0,
+					y: object.y + object.height / 2.0,
+					interval: get_float_property(&object.properties, "interval").unwrap_or(3.0),
+					max_alive: get_float_property(&object.properties, "max_alive").unwrap_or(1.0) as usize,
+					total: get_float_property(&object.properties, "total")
+						.map(|total| total as u32)
+						.filter(|total| *total > 0),
+					radius: get_float_property(&object.properties, "radius").unwrap_or(400.0),
+					properties: object.properties.clone(),
+				};
+				debug!(target: "game_engine", "{:?}", spawner);
+				game_map.add_spawner(spawner);
+				continue;
+			}
+
 			let mut actor_type = ActorType::NOTYPE;
 
 			if object.obj_type == "enemy" {
@@ -376,16 +613,122 @@ fn initialise_map(world: &mut World) {
 
 			let enemy = Actor::new(object.x, object.y, actor_type);
 			debug!(target: "game_engine", "{:?}", enemy);
-			game_map.add_actor(enemy);
+			game_map.add_actor(MapActor {
+				actor: enemy,
+				properties: object.properties.clone(),
+			});
 		}
 	}
 	
 	debug!(target: "game_engine", "{:?}", game_map);
 
-	debug!(target: "game_engine", "PLAYER DATA: {:?}", game_map.get_player());
+	initialise_platforms(world, &game_map, &tileset_sheets);
+
+	world.add_resource(NavGraphs::default());
 	world.add_resource(game_map);
 }
 
+fn initialise_platforms(world: &mut World, game_map: &GameMap, tileset_sheets: &[TilesetSheet]) {
+	let tile_size = game_map.tile_size as f32;
+
+	for map_platform in &game_map.platforms {
+		let width = tile_size * map_platform.tiles as f32;
+		let start = map_platform.path[0];
+
+		let mut platform_transform = Transform::default();
+		platform_transform.set_xyz(start.0, start.1, 0.05);
+
+		let platform = world
+			.create_entity()
+			.with(Platform::new(map_platform, width, tile_size))
+			.with(LevelEntity)
+			.with(GlobalTransform::default())
+			.with(platform_transform)
+			.build();
+
+		let tileset_render = match get_tile_sprite(tileset_sheets, map_platform.gid) {
+			Some(tileset_render) => tileset_render,
+			None => continue,
+		};
+
+		// The tiles are children of the platform so they move along with it
+		for tile in 0..map_platform.tiles {
+			let mut tile_transform = Transform::default();
+			tile_transform.set_xyz(tile_size * (tile as f32 + 0.5) - width / 2.0, 0.0, 0.0);
+
+			world
+				.create_entity()
+				.with(tileset_render.clone())
+				.with(Parent { entity: platform })
+				.with(LevelEntity)
+				.with(GlobalTransform::default())
+				.with(tile_transform)
+				.build();
+		}
+	}
+}
+
+/// Collision shapes drawn on a tile in the Tiled tileset editor, relative to the
+/// top left corner of the tile. Polygons and ellipses use their bounding box.
+fn get_tile_hitboxes(tile: &Tile) -> Vec<Rect> {
+	let mut hitboxes = Vec::new();
+
+	let object_group = match &tile.objectgroup {
+		Some(object_group) => object_group,
+		None => return hitboxes,
+	};
+
+	for object in &object_group.objects {
+		let hitbox = match &object.shape {
+			ObjectShape::Rect { width, height } | ObjectShape::Ellipse { width, height } => {
+				Some(Rect::new(object.x, object.y, *width, *height))
+			}
+			ObjectShape::Polygon { points } => {
+				Rect::bounding(points).map(|bounds| bounds.translate(object.x, object.y))
+			}
+			_ => None,
+		};
+
+		if let Some(hitbox) = hitbox {
+			hitboxes.push(hitbox);
+		}
+	}
+
+	hitboxes
+}
+
+fn get_bool_property(properties: &Properties, name: &str) -> Option<bool> {
+	match properties.get(name) {
+		Some(PropertyValue::BoolValue(value)) => Some(*value),
+		_ => None,
+	}
+}
+
+fn get_float_property(properties: &Properties, name: &str) -> Option<f32> {
+	match properties.get(name) {
+		Some(PropertyValue::FloatValue(value)) => Some(*value),
+		Some(PropertyValue::IntValue(value)) => Some(*value as f32),
+		_ => None,
+	}
+}
+
+/// Slopes are marked with `slope_left` and `slope_right` properties, for example
+/// 0.0 and 1.0 for a 45 degree slope rising to the right, or 0.0 and 0.5 for the
+/// lower half of a 22.5 degree one.
+fn get_slope_property(properties: &Properties) -> Option<(f32, f32)> {
+	match (get_float_property(properties, "slope_left"), get_float_property(properties, "slope_right")) {
+		(Some(left), Some(right)) => Some((left, right)),
+		_ => None,
+	}
+}
+
+fn get_string_property(properties: &Properties, name: &str) -> Option<String> {
+	match properties.get(name) {
+		Some(PropertyValue::StringValue(value)) => Some(value.clone()),
+		_ => None,
+	}
+}
+
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ActorType {
@@ -399,7 +742,35 @@ pub struct Actor {
     pub width: f32,
     pub height: f32,
     pub v_velocity: f32,
+    pub h_velocity: f32,
+    /// Direction the actor wants to move in, from -1.0 (left) to 1.0 (right).
+    pub h_input: f32,
+    pub acceleration: f32,
+    pub deceleration: f32,
+    pub max_speed: f32,
+    /// Multiplier for acceleration and deceleration while in the air.
+    pub air_control: f32,
+    pub jump: JumpSettings,
+    /// Time left to jump after walking off a ledge.
+    pub coyote_timer: f32,
+    /// Time left for a buffered jump press to trigger on landing.
+    pub jump_buffer_timer: f32,
+    /// Rising from a jump that can still be cut short.
+    pub jumping: bool,
+    /// Time left to fall through one-way platforms.
+    pub drop_timer: f32,
+    /// Moving platform the actor is standing on.
+    pub carried_by: Option<Entity>,
+    /// Holding on to a ladder, gravity doesn't apply.
+    pub climbing: bool,
+    pub climb_speed: f32,
     pub standing: bool,
+    pub contacts: Contacts,
+    /// Position resolved by the `ActorSystem` on its last run, the next run
+    /// sweeps from here to wherever the other systems moved the actor.
+    pub position: Option<(f32, f32)>,
+    /// Where the last `ActorSystem` sweep started from.
+    pub previous_position: Option<(f32, f32)>,
     pub spawn: (f32, f32),
     pub actor_type: ActorType,
 }
@@ -410,38 +781,112 @@ impl Actor {
             width: 32.0,
             height: 32.0,
             v_velocity: 5.0,
+            h_velocity: 0.0,
+            h_input: 0.0,
+            acceleration: 1200.0,
+            deceleration: 1600.0,
+            max_speed: 180.0,
+            air_control: 0.6,
+            jump: JumpSettings::default(),
+            coyote_timer: 0.0,
+            jump_buffer_timer: 0.0,
+            jumping: false,
+            drop_timer: 0.0,
+            carried_by: None,
+            climbing: false,
+            climb_speed: 150.0,
             standing: false,
+            contacts: Contacts::default(),
+            position: None,
+            previous_position: None,
             spawn: (x, y),
             actor_type: actor_type,
         }
     }
+
+    pub fn rect(&self, transform: &Transform) -> Rect {
+        Rect::from_center(
+            transform.translation().x,
+            transform.translation().y,
+            self.width,
+            self.height,
+        )
+    }
+
+    /// Rect at the start of the last move, the current one if the actor hasn't moved yet.
+    pub fn previous_rect(&self, transform: &Transform) -> Rect {
+        match self.previous_position {
+            Some((x, y)) => Rect::from_center(x, y, self.width, self.height),
+            None => self.rect(transform),
+        }
+    }
 }
 
-pub struct Player {}
+#[derive(Debug, Clone)]
+pub struct JumpSettings {
+    pub velocity: f32,
+    /// Multiplier for the upward velocity when the jump is released early.
+    pub cut_multiplier: f32,
+    pub coyote_time: f32,
+    pub buffer_time: f32,
+}
+
+impl Default for JumpSettings {
+    fn default() -> Self {
+        JumpSettings {
+            velocity: -600.0,
+            cut_multiplier: 0.5,
+            coyote_time: 0.1,
+            buffer_time: 0.1,
+        }
+    }
+}
+
+pub struct Player {
+    pub jump_was_down: bool,
+}
 
 impl Player {
     fn new() -> Player {
-        Player {}
+        Player {
+            jump_was_down: false,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct GameMap {
+    pub level: String,
     pub width: usize,
     pub height: usize,
     pub tile_size: usize,
     pub layers: Vec<MapLayer>,
-    pub actors: Vec<Actor>,
+    pub collision_layer: Option<usize>,
+    pub tile_attributes: HashMap<u32, TileAttributes>,
+    pub tile_hitboxes: HashMap<u32, Vec<Rect>>,
+    pub actors: Vec<MapActor>,
+    pub exits: Vec<MapExit>,
+    pub platforms: Vec<MapPlatform>,
+    pub spawners: Vec<MapSpawner>,
+    pub spawn_points: HashMap<String, (f32, f32)>,
 }
 
 impl GameMap {
-    fn new(width: usize, height: usize) -> GameMap {
+    fn new(level: &str, width: usize, height: usize) -> GameMap {
         GameMap {
+            level: level.to_string(),
             width: width,
             height: height,
             layers: Vec::new(),
+            collision_layer: None,
+            tile_attributes: HashMap::new(),
+            tile_hitboxes: HashMap::new(),
             tile_size: 32,
             actors: Vec::new(),
+            exits: Vec::new(),
+            platforms: Vec::new(),
+            spawners: Vec::new(),
+            spawn_points: HashMap::new(),
         }
     }
 
@@ -449,29 +894,241 @@ impl GameMap {
         self.layers.push(map_layer);
     }
 
-    fn add_actor(&mut self, actor: Actor) {
+    fn find_collision_layer(&mut self) {
+        self.collision_layer = self.layers.iter().position(|layer| layer.is_collision());
+
+        if self.collision_layer.is_none() {
+            warn!(target: "game_engine", "No collision layer found in level {:?}", self.level);
+        }
+    }
+
+    pub fn collision_layer(&self) -> Option<&MapLayer> {
+        self.collision_layer.map(|index| &self.layers[index])
+    }
+
+    fn set_tile_attributes(&mut self, gid: u32, attributes: TileAttributes) {
+        self.tile_attributes.insert(gid, attributes);
+    }
+
+    pub fn get_tile_attributes(&self, gid: u32) -> TileAttributes {
+        self.tile_attributes.get(&gid).cloned().unwrap_or_default()
+    }
+
+    /// Attributes of the collision layer tile at the given tile coordinates.
+    /// Tiles outside the map and maps without a collision layer have no attributes.
+    pub fn get_collision_tile(&self, x: usize, y: usize) -> TileAttributes {
+        let collision_layer = match self.collision_layer() {
+            Some(collision_layer) => collision_layer,
+            None => return TileAttributes::default(),
+        };
+
+        match collision_layer.tiles.get(y).and_then(|row| row.get(x)) {
+            Some(gid) => self.get_tile_attributes(*gid),
+            None => TileAttributes::default(),
+        }
+    }
+
+    fn set_tile_hitboxes(&mut self, gid: u32, hitboxes: Vec<Rect>) {
+        self.tile_hitboxes.insert(gid, hitboxes);
+    }
+
+    /// World space hitboxes of the collision layer tile at the given tile coordinates.
+    /// Tiles without collision shapes fill their whole cell.
+    pub fn get_tile_hitboxes(&self, x: usize, y: usize) -> Vec<Rect> {
+        let tile_size = self.tile_size as f32;
+        let cell = Rect::new(x as f32 * tile_size, y as f32 * tile_size, tile_size, tile_size);
+
+        let gid = self
+            .collision_layer()
+            .and_then(|layer| layer.tiles.get(y))
+            .and_then(|row| row.get(x));
+
+        match gid.and_then(|gid| self.tile_hitboxes.get(gid)) {
+            Some(hitboxes) => hitboxes
+                .iter()
+                .map(|hitbox| hitbox.translate(cell.x, cell.y))
+                .collect(),
+            None => vec![cell],
+        }
+    }
+
+    /// Z coordinate for the tiles of a layer. Layers up to the collision layer are
+    /// drawn behind the actors and the layers after it in front of them.
+    fn layer_depth(&self, layer_index: usize) -> f32 {
+        let collision_index = self.collision_layer.unwrap_or(self.layers.len() - 1);
+
+        if layer_index <= collision_index {
+            -0.01 * (collision_index - layer_index) as f32
+        } else {
+            0.2 + 0.01 * (layer_index - collision_index) as f32
+        }
+    }
+
+    fn add_actor(&mut self, actor: MapActor) {
         self.actors.push(actor);
     }
 
-    fn get_player(&self) -> &Actor {
-        for actor in &self.actors {
-            if actor.actor_type == ActorType::PLAYER {
-                return actor;
-            }
+    fn add_exit(&mut self, exit: MapExit) {
+        self.exits.push(exit);
+    }
+
+    fn add_platform(&mut self, platform: MapPlatform) {
+        self.platforms.push(platform);
+    }
+
+    fn add_spawner(&mut self, spawner: MapSpawner) {
+        self.spawners.push(spawner);
+    }
+
+    fn add_spawn_point(&mut self, name: &str, position: (f32, f32)) {
+        self.spawn_points.insert(name.to_string(), position);
+    }
+
+    pub fn get_spawn_point(&self, name: &str) -> Option<(f32, f32)> {
+        self.spawn_points.get(name).cloned()
+    }
+
+    /// The `player` object of the map. Levels entered only through named spawn points may not have one.
+    pub fn get_player(&self) -> Option<&Actor> {
+        self.actors
+            .iter()
+            .map(|map_actor| &map_actor.actor)
+            .find(|actor| actor.actor_type == ActorType::PLAYER)
+    }
+}
+
+/// Gameplay flags of a tile, read from the per-tile properties of its tileset.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TileAttributes {
+    pub solid: bool,
+    pub one_way: bool,
+    pub hazard: bool,
+    pub ladder: bool,
+    pub water: bool,
+    pub slippery: bool,
+    /// Surface height at the left and right edge of a slope tile, as a fraction
+    /// of the tile height measured from its bottom.
+    pub slope: Option<(f32, f32)>,
+}
+
+impl TileAttributes {
+    fn from_properties(properties: &Properties) -> TileAttributes {
+        TileAttributes {
+            solid: get_bool_property(properties, "solid").unwrap_or(false),
+            one_way: get_bool_property(properties, "one_way").unwrap_or(false),
+            hazard: get_bool_property(properties, "hazard").unwrap_or(false),
+            ladder: get_bool_property(properties, "ladder").unwrap_or(false),
+            water: get_bool_property(properties, "water").unwrap_or(false),
+            slippery: get_bool_property(properties, "slippery").unwrap_or(false),
+            slope: get_slope_property(properties),
         }
+    }
+}
 
-        panic!("Couldn't find player data in the gamemap data");
+/// Actor spawn read from a Tiled object, with the object's custom properties.
+#[derive(Debug, Clone)]
+pub struct MapActor {
+    pub actor: Actor,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone)]
+pub struct MapExit {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub target_level: String,
+    pub target_spawn: Option<String>,
+}
+
+impl MapExit {
+    pub fn overlaps(&self, x: f32, y: f32, width: f32, height: f32) -> bool {
+        x - width / 2.0 < self.x + self.width
+            && x + width / 2.0 > self.x
+            && y - height / 2.0 < self.y + self.height
+            && y + height / 2.0 > self.y
+    }
+}
+
+/// Moving platform read from a `platform` object with a polyline path in Tiled.
+#[derive(Debug, Clone)]
+pub struct MapPlatform {
+    /// Points the centre of the platform moves through, in world coordinates.
+    pub path: Vec<(f32, f32)>,
+    pub speed: f32,
+    /// Go back and forth along the path instead of looping back to its start.
+    pub ping_pong: bool,
+    /// Width of the platform in tiles.
+    pub tiles: usize,
+    pub gid: u32,
+}
+
+/// Enemy spawner read from a `spawner` object in Tiled. The enemies use the
+/// archetype named by its `archetype` property.
+#[derive(Debug, Clone)]
+pub struct MapSpawner {
+    pub x: f32,
+    pub y: f32,
+    /// Seconds between spawns.
+    pub interval: f32,
+    /// Most enemies of this spawner alive at the same time.
+    pub max_alive: usize,
+    /// Enemies spawned before the spawner runs out, unlimited when not set.
+    pub total: Option<u32>,
+    /// Distance to the player within which the spawner is active, zero is always active.
+    pub radius: f32,
+    pub properties: Properties,
+}
+
+/// Requested change of level, picked up by the `Sidescroller` state after the
+/// running systems have been dispatched.
+#[derive(Debug, Default)]
+pub struct LevelTransition {
+    pub target: Option<(String, Option<String>)>,
+}
+
+/// Random number generator for all gameplay randomness. It is seeded so the
+/// same seed and inputs play out the same way. PCG is a fixed algorithm, unlike
+/// `StdRng`, so seeds keep reproducing runs across `rand` releases.
+pub struct GameRng {
+    pub seed: u64,
+    pub rng: Pcg32,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        GameRng {
+            seed: seed,
+            rng: Pcg32::seed_from_u64(seed),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct MapLayer {
+    pub name: String,
+    pub properties: Properties,
     pub tiles: Vec<Vec<u32>>,
 }
 
 impl MapLayer {
-    fn new(tiles: Vec<Vec<u32>>) -> MapLayer {
-        MapLayer { tiles: tiles }
+    fn new(name: &str, properties: Properties, tiles: Vec<Vec<u32>>) -> MapLayer {
+        MapLayer {
+            name: name.to_string(),
+            properties: properties,
+            tiles: tiles,
+        }
+    }
+
+    /// A layer is used for collision when it is named "collision" or has a
+    /// `collision` bool property set in Tiled.
+    pub fn is_collision(&self) -> bool {
+        if let Some(PropertyValue::BoolValue(collision)) = self.properties.get("collision") {
+            return *collision;
+        }
+
+        self.name.eq_ignore_ascii_case("collision")
     }
 }
 
@@ -483,14 +1140,373 @@ impl Component for Actor {
     type Storage = DenseVecStorage<Self>;
 }
 
-pub struct Enemy {}
+/// What an enemy does while it hasn't noticed the player.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnemyBehaviour {
+    /// Stands still.
+    IDLE,
+    /// Jumps in place at random.
+    JUMP,
+    /// Walks back and forth, turning around at walls and optionally at ledges.
+    PATROL,
+}
+
+impl EnemyBehaviour {
+    fn from_name(name: &str) -> EnemyBehaviour {
+        match name {
+            "idle" => EnemyBehaviour::IDLE,
+            "jump" => EnemyBehaviour::JUMP,
+            _ => EnemyBehaviour::PATROL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnemyState {
+    /// Following its `EnemyBehaviour`.
+    WANDERING,
+    /// Running after the player it has seen.
+    CHASING,
+}
+
+pub struct Enemy {
+    /// Name of the archetype the enemy was spawned from.
+    pub archetype: String,
+    pub behaviour: EnemyBehaviour,
+    pub state: EnemyState,
+    pub speed: f32,
+    pub turn_at_ledges: bool,
+    /// Walking direction, -1.0 for left and 1.0 for right.
+    pub direction: f32,
+    /// How far away the player can be noticed, zero never chases.
+    pub sight_range: f32,
+    /// How long a chase goes on after losing sight of the player.
+    pub give_up_time: f32,
+    pub lost_sight_timer: f32,
+    /// Link of the path to the player that is being followed.
+    pub nav_link: Option<NavLink>,
+    /// Damage dealt to the player on contact.
+    pub damage: u32,
+}
 
 impl Enemy {
-    fn new() -> Enemy {
-        Enemy {}
+    /// Starts from the archetype and lets the `behaviour`, `speed`, `turn_at_ledges`,
+    /// `sight_range` and `give_up_time` properties of the enemy object in Tiled override it.
+    fn new(archetype: &EnemyArchetype, properties: &Properties) -> Enemy {
+        let behaviour = get_string_property(properties, "behaviour").unwrap_or_else(|| archetype.behaviour.clone());
+
+        Enemy {
+            archetype: archetype.name.clone(),
+            behaviour: EnemyBehaviour::from_name(&behaviour),
+            state: EnemyState::WANDERING,
+            speed: get_float_property(properties, "speed").unwrap_or(archetype.speed),
+            turn_at_ledges: get_bool_property(properties, "turn_at_ledges").unwrap_or(archetype.turn_at_ledges),
+            direction: 1.0,
+            sight_range: get_float_property(properties, "sight_range").unwrap_or(archetype.sight_range),
+            give_up_time: get_float_property(properties, "give_up_time").unwrap_or(archetype.give_up_time),
+            lost_sight_timer: 0.0,
+            nav_link: None,
+            damage: archetype.damage,
+        }
     }
 }
 
 impl Component for Enemy {
     type Storage = DenseVecStorage<Self>;
 }
+
+/// Marks entities that belong to the current level and are removed when the level changes.
+#[derive(Default)]
+pub struct LevelEntity;
+
+impl Component for LevelEntity {
+    type Storage = NullStorage<Self>;
+}
+
+pub struct Platform {
+    pub path: Vec<(f32, f32)>,
+    pub speed: f32,
+    pub ping_pong: bool,
+    pub width: f32,
+    pub height: f32,
+    /// Index of the path point the platform is moving towards.
+    pub target: usize,
+    /// Whether a ping-pong platform is going back towards the start of the path.
+    pub reversing: bool,
+    /// How far the platform moved on the last tick, actors standing on it move as much.
+    pub delta: (f32, f32),
+}
+
+impl Platform {
+    fn new(map_platform: &MapPlatform, width: f32, height: f32) -> Platform {
+        Platform {
+            path: map_platform.path.clone(),
+            speed: map_platform.speed,
+            ping_pong: map_platform.ping_pong,
+            width: width,
+            height: height,
+            target: if map_platform.path.len() > 1 { 1 } else { 0 },
+            reversing: false,
+            delta: (0.0, 0.0),
+        }
+    }
+}
+
+impl Component for Platform {
+    type Storage = DenseVecStorage<Self>;
+}
+
+pub struct Spawner {
+    pub archetype: EnemyArchetype,
+    /// Custom properties of the spawner object, every spawned enemy reads them like an enemy object's.
+    pub properties: Properties,
+    pub sprite_sheet: SpriteSheetHandle,
+    pub interval: f32,
+    pub max_alive: usize,
+    pub total: Option<u32>,
+    pub radius: f32,
+    /// Time left until the next spawn, only counting down while below `max_alive`.
+    pub timer: f32,
+    pub spawned: u32,
+    pub alive: Vec<Entity>,
+}
+
+impl Spawner {
+    fn new(map_spawner: &MapSpawner, archetype: EnemyArchetype, sprite_sheet: SpriteSheetHandle) -> Spawner {
+        Spawner {
+            archetype: archetype,
+            properties: map_spawner.properties.clone(),
+            sprite_sheet: sprite_sheet,
+            interval: map_spawner.interval,
+            max_alive: map_spawner.max_alive,
+            total: map_spawner.total,
+            radius: map_spawner.radius,
+            timer: 0.0,
+            spawned: 0,
+            alive: Vec::new(),
+        }
+    }
+
+    /// Spawned its total and won't spawn anymore.
+    pub fn is_exhausted(&self) -> bool {
+        self.total.map_or(false, |total| self.spawned >= total)
+    }
+}
+
+impl Component for Spawner {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Debug, Clone)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+    /// Damage is ignored while this is above zero.
+    pub invulnerable_timer: f32,
+    pub invulnerable_time: f32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Health {
+        Health {
+            current: max,
+            max: max,
+            invulnerable_timer: 0.0,
+            invulnerable_time: 1.5,
+        }
+    }
+}
+
+impl Component for Health {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Debug, Clone)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+    /// Kills the target regardless of its health and invulnerability.
+    pub fatal: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amethyst::core::timing::Time;
+    use amethyst::ecs::{ReadStorage, RunNow};
+    use amethyst::input::{Bindings, InputEvent, InputHandler};
+    use amethyst::shrev::EventChannel;
+    use amethyst::winit::{DeviceId, ElementState, Event, KeyboardInput, ModifiersState, WindowEvent, WindowId};
+
+    use crate::systems::{ActorSystem, EnemySystem, GravitySystem, MovementSystem, PlayerSystem};
+
+    const TICKS: usize = 300;
+
+    /// Ten tiles wide with a solid floor on the bottom row.
+    fn flat_map(height: usize) -> GameMap {
+        let mut game_map = GameMap::new("test", 10, height);
+
+        let mut tiles = vec![vec![0; 10]; height];
+        tiles[height - 1] = vec![1; 10];
+
+        game_map.push(MapLayer::new("collision", Properties::new(), tiles));
+        game_map.set_tile_attributes(1, TileAttributes {
+            solid: true,
+            ..TileAttributes::default()
+        });
+        game_map.find_collision_layer();
+
+        game_map
+    }
+
+    /// Runs randomly jumping and patrolling enemies for a few seconds and
+    /// returns where they end up.
+    fn simulate(seed: u64) -> Vec<(f32, f32)> {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Actor>();
+        world.register::<Enemy>();
+        world.register::<Player>();
+        world.register::<Platform>();
+
+        let mut time = Time::default();
+        time.set_fixed_seconds(1.0 / 60.0);
+
+        world.add_resource(time);
+        world.add_resource(flat_map(6));
+        world.add_resource(NavGraphs::default());
+        world.add_resource(GameRng::new(seed));
+
+        for (x, behaviour) in &[(48.0, "jump"), (144.0, "jump"), (240.0, "patrol")] {
+            let archetype = EnemyArchetype {
+                behaviour: behaviour.to_string(),
+                sight_range: 0.0,
+                ..EnemyArchetype::default()
+            };
+
+            let mut transform = Transform::default();
+            transform.set_xyz(*x, 144.0, 0.0);
+
+            let enemy = Enemy::new(&archetype, &Properties::new());
+            let mut actor = Actor::new(*x, 144.0, ActorType::ENEMY);
+            actor.max_speed = enemy.speed;
+
+            world.create_entity().with(actor).with(enemy).with(transform).build();
+        }
+
+        for _ in 0..TICKS {
+            EnemySystem.run_now(&world.res);
+            MovementSystem.run_now(&world.res);
+            GravitySystem.run_now(&world.res);
+            ActorSystem.run_now(&world.res);
+            world.maintain();
+        }
+
+        world.exec(|(transforms, actors): (ReadStorage<Transform>, ReadStorage<Actor>)| {
+            (&transforms, &actors)
+                .join()
+                .map(|(transform, _)| (transform.translation().x, transform.translation().y))
+                .collect()
+        })
+    }
+
+    /// Keyboard event as winit delivers it to the game window.
+    fn key_event(key: VirtualKeyCode, state: ElementState) -> Event {
+        // The dummy ids are only compared, never handed back to winit
+        let (window_id, device_id) = unsafe { (WindowId::dummy(), DeviceId::dummy()) };
+
+        Event::WindowEvent {
+            window_id: window_id,
+            event: WindowEvent::KeyboardInput {
+                device_id: device_id,
+                input: KeyboardInput {
+                    scancode: 0,
+                    state: state,
+                    virtual_keycode: Some(key),
+                    modifiers: ModifiersState::default(),
+                },
+            },
+        }
+    }
+
+    /// Holds right on the ground for half a second, then lets go and jumps,
+    /// using the game's key bindings. Returns where the player is after a second.
+    fn run_player(tick_rate: u32) -> (f32, f32) {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Actor>();
+        world.register::<Player>();
+        world.register::<Platform>();
+
+        let mut time = Time::default();
+        time.set_fixed_seconds(1.0 / tick_rate as f32);
+
+        let mut input = InputHandler::<String, String>::new();
+        input.bindings = Bindings::load(format!(
+            "{}/resources/bindings_config.ron",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+
+        world.add_resource(time);
+        world.add_resource(flat_map(12));
+        world.add_resource(input);
+
+        let mut transform = Transform::default();
+        transform.set_xyz(48.0, 336.0, 0.0);
+
+        let mut actor = Actor::new(48.0, 336.0, ActorType::PLAYER);
+        actor.standing = true;
+        actor.v_velocity = 0.0;
+
+        world.create_entity().with(actor).with(Player::new()).with(transform).build();
+
+        let mut input_events = EventChannel::<InputEvent<String>>::new();
+        let mut press = |world: &World, key: VirtualKeyCode, state: ElementState| {
+            world
+                .write_resource::<InputHandler<String, String>>()
+                .send_event(&key_event(key, state), &mut input_events, 1.0);
+        };
+
+        press(&world, VirtualKeyCode::D, ElementState::Pressed);
+
+        for tick in 0..tick_rate {
+            if tick == tick_rate / 2 {
+                press(&world, VirtualKeyCode::D, ElementState::Released);
+                press(&world, VirtualKeyCode::Space, ElementState::Pressed);
+            }
+
+            PlayerSystem.run_now(&world.res);
+            MovementSystem.run_now(&world.res);
+            GravitySystem.run_now(&world.res);
+            ActorSystem.run_now(&world.res);
+            world.maintain();
+        }
+
+        world.exec(|(transforms, players): (ReadStorage<Transform>, ReadStorage<Player>)| {
+            (&transforms, &players)
+                .join()
+                .map(|(transform, _)| (transform.translation().x, transform.translation().y))
+                .next()
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn same_seed_ends_in_the_same_positions() {
+        assert_eq!(simulate(42), simulate(42));
+        assert_ne!(simulate(42), simulate(7));
+    }
+
+    #[test]
+    fn same_distance_at_any_tick_rate() {
+        let reference = run_player(60);
+
+        for tick_rate in &[30, 144] {
+            let (x, y) = run_player(*tick_rate);
+
+            assert!((x - reference.0).abs() < 0.5, "x {} at {} Hz, {} at 60 Hz", x, tick_rate, reference.0);
+            assert!((y - reference.1).abs() < 0.5, "y {} at {} Hz, {} at 60 Hz", y, tick_rate, reference.1);
+        }
+    }
+}
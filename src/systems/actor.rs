@@ -1,94 +1,109 @@
 use amethyst::core::Transform;
-use amethyst::ecs::{Join, ReadExpect, System, WriteStorage};
+use amethyst::ecs::{Entities, Entity, Join, ReadExpect, ReadStorage, System, WriteStorage};
 
+use crate::collision;
+use crate::collision::{MoveOptions, Rect};
 use crate::sidescroller::Actor;
 use crate::sidescroller::GameMap;
-use crate::sidescroller::MapLayer;
+use crate::sidescroller::Platform;
 
 pub struct ActorSystem;
 impl<'s> System<'s> for ActorSystem {
 	type SystemData = (
+		Entities<'s>,
 		WriteStorage<'s, Transform>,
 		WriteStorage<'s, Actor>,
+		ReadStorage<'s, Platform>,
 		ReadExpect<'s, GameMap>,
 	);
 
-	fn run(&mut self, (mut transforms, mut actors, game_map): Self::SystemData) {
-		for (actor, transform) in (&mut actors, &mut transforms).join() {
-			// Avoid out of bounds from map
-			if transform.translation().x <= 1.0 {
-				transform.set_x(1.0);
-			}
+	fn run(&mut self, (entities, mut transforms, mut actors, platforms, game_map): Self::SystemData) {
+		let map_width = (game_map.width * game_map.tile_size) as f32;
 
-			if transform.translation().x >= ((&game_map.width - 1) * 32) as f32 {
-				transform.set_x(((&game_map.width - 1) * 32) as f32);
-			}
-
-			// Calculate tile coordinates for the actor
-			
-			let tile_size_as_f32 = &(game_map.tile_size as f32);
+		let moving_platforms: Vec<(Entity, Rect, (f32, f32))> = (&entities, &platforms, &transforms)
+			.join()
+			.map(|(entity, platform, transform)| {
+				let rect = Rect::from_center(
+					transform.translation().x,
+					transform.translation().y,
+					platform.width,
+					platform.height,
+				);
+				(entity, rect, platform.delta)
+			})
+			.collect();
 
-			let mut tile_x =
-				(transform.translation().x / &(game_map.tile_size as f32)).floor() as usize;
-			let mut tile_x_right = (((transform.translation().x) + tile_size_as_f32)
-				/ tile_size_as_f32)
-				.floor() as usize;
-			let tile_y = ((transform.translation().y + tile_size_as_f32 / 2.0) / tile_size_as_f32).floor()
-					as usize;
+		let dynamic_solids: Vec<Rect> = moving_platforms.iter().map(|(_, rect, _)| *rect).collect();
 
-			let collision_layer = &game_map.layers[1];
+		for (actor, transform) in (&mut actors, &mut transforms).join() {
+			let mut current = (transform.translation().x, transform.translation().y);
+			let previous = actor.position.unwrap_or(current);
 
-			if tile_x_right > &game_map.width - 1 {
-				tile_x_right = &game_map.width - 1;
+			// Actors standing on a platform are carried along with it
+			if let Some(carrier) = actor.carried_by {
+				if let Some((_, _, delta)) = moving_platforms.iter().find(|(entity, _, _)| *entity == carrier) {
+					current.0 += delta.0;
+					current.1 += delta.1;
+				}
 			}
 
-			// Collision system
-			if is_colliding(collision_layer, tile_x_right, tile_y) {
-				transform.set_x(((tile_x_right - 1) * &(game_map.tile_size)) as f32);
-				// debug!(target: "game_engine", "RIGHT COLLIDE");
+			// Sweep from where the actor was resolved last time to where the other systems moved it
+			let actor_rect = Rect::from_center(previous.0, previous.1, actor.width, actor.height);
+			let (mut actor_rect, contacts) = collision::move_and_collide(
+				&game_map,
+				&actor_rect,
+				current.0 - previous.0,
+				current.1 - previous.1,
+				MoveOptions {
+					one_way: actor.drop_timer <= 0.0 && !actor.climbing,
+					grounded: actor.standing,
+				},
+				&dynamic_solids,
+			);
+
+			// Avoid out of bounds from map
+			if actor_rect.left() < 0.0 {
+				actor_rect.x = 0.0;
 			}
 
-			if is_colliding(collision_layer, tile_x, tile_y) {
-				transform.set_x(((tile_x + 1) * &(game_map.tile_size)) as f32);
-				// debug!(target: "game_engine", "LEFT COLLIDE");
+			if actor_rect.right() > map_width {
+				actor_rect.x = map_width - actor_rect.width;
 			}
 
-			tile_x =
-				((transform.translation().x + 1.0) / &(game_map.tile_size as f32)).floor() as usize;
-			tile_x_right = (((transform.translation().x) + tile_size_as_f32 - 1.0)
-				/ tile_size_as_f32)
-				.floor() as usize;
-
-			// TODO: Somehow refactor these ifs
-			// Downward
-			if (is_colliding(collision_layer, tile_x, tile_y + 1)
-				|| is_colliding(collision_layer, tile_x_right, tile_y + 1))
-				&& (transform.translation().y + tile_size_as_f32 / 2.0)
-					> ((tile_y) * &game_map.tile_size) as f32
-				&& actor.v_velocity >= 0.0
-			{
-				// debug!(target: "game_engine", "DOWN COLLIDE");
+			if contacts.bottom && actor.v_velocity >= 0.0 {
 				actor.v_velocity = 0.0;
 				actor.standing = true;
-				transform.set_y((tile_y * &game_map.tile_size) as f32);
-			} else if (is_colliding(collision_layer, tile_x, tile_y - 1)
-				|| is_colliding(collision_layer, tile_x_right, tile_y - 1))
-				&& (transform.translation().y - tile_size_as_f32 / 2.0)
-					< ((tile_y) * &game_map.tile_size) as f32
-				&& actor.v_velocity < 0.0
-			{
-				// Upwards
-				// debug!(target: "game_engine", "UP COLLIDE");
-				actor.v_velocity = 0.0;
-				actor.standing = false;
-				transform.set_y((tile_y * &game_map.tile_size) as f32);
 			} else {
 				actor.standing = false;
 			}
+
+			if contacts.top && actor.v_velocity < 0.0 {
+				actor.v_velocity = 0.0;
+			}
+
+			if (contacts.left && actor.h_velocity < 0.0) || (contacts.right && actor.h_velocity > 0.0) {
+				actor.h_velocity = 0.0;
+			}
+
+			actor.carried_by = if actor.standing {
+				moving_platforms
+					.iter()
+					.find(|(_, rect, _)| {
+						(rect.top() - actor_rect.bottom()).abs() < 0.5
+							&& rect.left() < actor_rect.right()
+							&& rect.right() > actor_rect.left()
+					})
+					.map(|(entity, _, _)| *entity)
+			} else {
+				None
+			};
+
+			actor.contacts = contacts;
+			actor.previous_position = Some(previous);
+			actor.position = Some((actor_rect.center_x(), actor_rect.center_y()));
+
+			transform.set_x(actor_rect.center_x());
+			transform.set_y(actor_rect.center_y());
 		}
 	}
 }
-
-fn is_colliding(layer: &MapLayer, x: usize, y: usize) -> bool {
-	layer.tiles[y][x] == 1
-}
@@ -0,0 +1,36 @@
+use amethyst::core::Transform;
+use amethyst::ecs::{Entities, Join, ReadExpect, ReadStorage, System, Write};
+use amethyst::shrev::EventChannel;
+
+use crate::collision::{OverlapEvent, SpatialHash};
+use crate::sidescroller::Actor;
+use crate::sidescroller::GameMap;
+
+/// Rebuilds the `SpatialHash` of all actors every tick and publishes an
+/// `OverlapEvent` for each pair of overlapping actors.
+pub struct BroadphaseSystem;
+impl<'s> System<'s> for BroadphaseSystem {
+	type SystemData = (
+		Entities<'s>,
+		ReadStorage<'s, Actor>,
+		ReadStorage<'s, Transform>,
+		ReadExpect<'s, GameMap>,
+		Write<'s, SpatialHash>,
+		Write<'s, EventChannel<OverlapEvent>>,
+	);
+
+	fn run(
+		&mut self,
+		(entities, actors, transforms, game_map, mut spatial_hash, mut overlap_events): Self::SystemData,
+	) {
+		spatial_hash.clear(game_map.tile_size as f32);
+
+		for (entity, actor, transform) in (&entities, &actors, &transforms).join() {
+			spatial_hash.insert(entity, actor.rect(transform));
+		}
+
+		for (a, b) in spatial_hash.overlapping_pairs() {
+			overlap_events.single_write(OverlapEvent { a: a, b: b });
+		}
+	}
+}
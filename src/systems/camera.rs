@@ -0,0 +1,39 @@
+use amethyst::core::Transform;
+use amethyst::ecs::{Join, ReadStorage, System, WriteStorage};
+use amethyst::renderer::Camera;
+
+use crate::sidescroller::Player;
+use crate::sidescroller::{CAMERA_HEIGHT, CAMERA_WIDTH};
+
+pub struct CameraSystem;
+impl<'s> System<'s> for CameraSystem {
+	type SystemData = (
+		WriteStorage<'s, Transform>,
+		ReadStorage<'s, Player>,
+		ReadStorage<'s, Camera>,
+	);
+
+	fn run(&mut self, (mut transforms, players, camera): Self::SystemData) {
+		let mut player_x = 0.0;
+		let mut player_y = 0.0;
+
+		for (_, transform) in (&players, &transforms).join() {
+			player_x = transform.translation().x;
+			player_y = transform.translation().y;
+		}
+
+		for (_, transform) in (&camera, &mut transforms).join() {
+
+			// Place camera view so that the player is in the middle
+			transform.set_x(player_x - (CAMERA_WIDTH/2.0));
+			transform.set_y(player_y - (CAMERA_HEIGHT/2.0));
+			if transform.translation().x < 0.0 {
+				transform.set_x(0.0);
+			}
+
+			if transform.translation().y < 0.0 {
+				transform.set_y(0.0);
+			}
+		}
+	}
+}
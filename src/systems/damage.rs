@@ -0,0 +1,152 @@
+use amethyst::core::Transform;
+use amethyst::ecs::prelude::{Resources, SystemData};
+use amethyst::ecs::{Entities, Entity, Join, Read, ReadExpect, ReadStorage, System, Write, WriteStorage};
+use amethyst::shrev::{EventChannel, ReaderId};
+
+use std::collections::HashMap;
+
+use crate::collision;
+use crate::collision::{OverlapEvent, Rect, SpatialHash};
+use crate::sidescroller::Actor;
+use crate::sidescroller::DamageEvent;
+use crate::sidescroller::Enemy;
+use crate::sidescroller::GameMap;
+use crate::sidescroller::Health;
+use crate::sidescroller::Player;
+
+/// Upward velocity of the player after stomping on an enemy.
+const STOMP_BOUNCE: f32 = -400.0;
+
+/// How far the player's feet may have been below the top of an enemy and still stomp it.
+const STOMP_TOLERANCE: f32 = 1.0;
+
+/// Publishes damage for players touching enemies or hazard tiles, and for
+/// anything that falls off the bottom of the map. Players landing on an enemy
+/// from above defeat it and bounce off it instead.
+///
+/// Touching is read from the `OverlapEvent`s of the `BroadphaseSystem`. Stomps
+/// query the `SpatialHash` along the player's move instead, since a fast fall
+/// can end past an enemy without overlapping it.
+#[derive(Default)]
+pub struct DamageSystem {
+	overlap_reader: Option<ReaderId<OverlapEvent>>,
+}
+
+impl<'s> System<'s> for DamageSystem {
+	type SystemData = (
+		Entities<'s>,
+		ReadStorage<'s, Transform>,
+		WriteStorage<'s, Actor>,
+		ReadStorage<'s, Player>,
+		ReadStorage<'s, Enemy>,
+		ReadStorage<'s, Health>,
+		ReadExpect<'s, GameMap>,
+		Read<'s, SpatialHash>,
+		Read<'s, EventChannel<OverlapEvent>>,
+		Write<'s, EventChannel<DamageEvent>>,
+	);
+
+	fn setup(&mut self, res: &mut Resources) {
+		Self::SystemData::setup(res);
+		self.overlap_reader = Some(res.fetch_mut::<EventChannel<OverlapEvent>>().register_reader());
+	}
+
+	fn run(
+		&mut self,
+		(entities, transforms, mut actors, players, enemies, healths, game_map, spatial_hash, overlap_events, mut damage_events): Self::SystemData,
+	) {
+		let map_height = (game_map.height * game_map.tile_size) as f32;
+
+		for (entity, actor, transform, _) in (&entities, &actors, &transforms, &healths).join() {
+			if actor.rect(transform).top() > map_height {
+				damage_events.single_write(DamageEvent {
+					target: entity,
+					amount: 0,
+					fatal: true,
+				});
+			}
+		}
+
+		// Enemies each player is touching at the end of this tick
+		let mut touching: HashMap<Entity, Vec<Entity>> = HashMap::new();
+
+		for event in overlap_events.read(self.overlap_reader.as_mut().unwrap()) {
+			for &(player, other) in &[(event.a, event.b), (event.b, event.a)] {
+				if players.contains(player) && enemies.contains(other) {
+					touching.entry(player).or_insert_with(Vec::new).push(other);
+				}
+			}
+		}
+
+		// Tops of the enemies before this tick's move
+		let enemy_tops: HashMap<Entity, f32> = (&entities, &enemies, &actors, &transforms)
+			.join()
+			.map(|(entity, _, actor, transform)| (entity, actor.previous_rect(transform).top()))
+			.collect();
+
+		for (entity, _, actor, transform, health) in (&entities, &players, &mut actors, &transforms, &healths).join() {
+			let player_rect = actor.rect(transform);
+			let previous_rect = actor.previous_rect(transform);
+			let falling = actor.v_velocity > 0.0;
+			let mut stomped = Vec::new();
+
+			// Everything the player moved through this tick, so a fast fall can't skip an enemy
+			let swept_rect = Rect::bounding(&[
+				(previous_rect.left(), previous_rect.top()),
+				(previous_rect.right(), previous_rect.bottom()),
+				(player_rect.left(), player_rect.top()),
+				(player_rect.right(), player_rect.bottom()),
+			])
+			.unwrap_or(player_rect);
+
+			for (enemy_entity, enemy_rect) in spatial_hash.query(&swept_rect) {
+				if !enemies.contains(enemy_entity) {
+					continue;
+				}
+
+				let enemy_top = enemy_tops.get(&enemy_entity).cloned().unwrap_or(enemy_rect.top());
+
+				// Falling onto an enemy whose top was below the player's feet before the move is a stomp
+				if falling && previous_rect.bottom() <= enemy_top + STOMP_TOLERANCE {
+					damage_events.single_write(DamageEvent {
+						target: enemy_entity,
+						amount: 0,
+						fatal: true,
+					});
+
+					actor.v_velocity = STOMP_BOUNCE;
+					actor.jumping = false;
+					stomped.push(enemy_entity);
+				}
+			}
+
+			let mut contact_damage = touching
+				.get(&entity)
+				.into_iter()
+				.flatten()
+				.filter(|enemy| !stomped.contains(*enemy))
+				.filter_map(|enemy| enemies.get(*enemy))
+				.map(|enemy| enemy.damage)
+				.max()
+				.unwrap_or(0);
+
+			if health.invulnerable_timer > 0.0 {
+				continue;
+			}
+
+			let touching_hazard = collision::overlaps_tiles(&game_map, &player_rect, |attributes| attributes.hazard);
+
+			if touching_hazard {
+				contact_damage = contact_damage.max(1);
+			}
+
+			if contact_damage > 0 {
+				damage_events.single_write(DamageEvent {
+					target: entity,
+					amount: contact_damage,
+					fatal: false,
+				});
+			}
+		}
+	}
+}
@@ -1,32 +1,185 @@
+use amethyst::core::timing::Time;
 use amethyst::core::Transform;
-use amethyst::ecs::{Join, System, WriteStorage};
+use amethyst::ecs::{Join, Read, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
 
-use rand::prelude::*;
+use rand::Rng;
+use rand_pcg::Pcg32;
 
+use crate::collision;
+use crate::navigation::{NavGraph, NavGraphs, NavLinkKind, NavSettings};
 use crate::sidescroller::Actor;
 use crate::sidescroller::Enemy;
+use crate::sidescroller::EnemyBehaviour;
+use crate::sidescroller::EnemyState;
+use crate::sidescroller::GameMap;
+use crate::sidescroller::GameRng;
+use crate::sidescroller::Player;
+
+const JUMPS_PER_SECOND: f32 = 3.0;
+
+/// Chasing enemies stop this close to the player horizontally instead of jittering around it.
+const CHASE_DEAD_ZONE: f32 = 4.0;
 
 pub struct EnemySystem;
 impl<'s> System<'s> for EnemySystem {
 	type SystemData = (
-		WriteStorage<'s, Transform>,
+		ReadStorage<'s, Transform>,
+		ReadStorage<'s, Player>,
 		WriteStorage<'s, Enemy>,
 		WriteStorage<'s, Actor>,
+		ReadExpect<'s, GameMap>,
+		WriteExpect<'s, NavGraphs>,
+		WriteExpect<'s, GameRng>,
+		Read<'s, Time>,
 	);
 
-	fn run(&mut self, (mut transforms, mut enemies, mut actors): Self::SystemData) {
-		for (_, actor, transform) in (&mut enemies, &mut actors, &mut transforms).join() {
+	fn run(
+		&mut self,
+		(transforms, players, mut enemies, mut actors, game_map, mut nav_graphs, mut game_rng, time): Self::SystemData,
+	) {
+		let player_position = (&players, &transforms)
+			.join()
+			.map(|(_, transform)| (transform.translation().x, transform.translation().y))
+			.next();
+
+		for (enemy, actor, transform) in (&mut enemies, &mut actors, &transforms).join() {
+			let position = (transform.translation().x, transform.translation().y);
 
-			let mut rng = thread_rng();
+			let sees_player = match player_position {
+				Some(player_position) => can_see(enemy, position, player_position, &game_map),
+				None => false,
+			};
+
+			match enemy.state {
+				EnemyState::WANDERING => {
+					if sees_player {
+						debug!(target: "game_engine", "Enemy at {:?} noticed the player", position);
+						enemy.state = EnemyState::CHASING;
+						enemy.lost_sight_timer = 0.0;
+					}
+				}
+				EnemyState::CHASING => {
+					if sees_player {
+						enemy.lost_sight_timer = 0.0;
+					} else {
+						enemy.lost_sight_timer += time.fixed_seconds();
+
+						if enemy.lost_sight_timer >= enemy.give_up_time {
+							debug!(target: "game_engine", "Enemy at {:?} gave up the chase", position);
+							enemy.state = EnemyState::WANDERING;
+						}
+					}
+				}
+			}
+
+			match (&enemy.state, player_position) {
+				(EnemyState::CHASING, Some(player_position)) => {
+					// Paths only use the jumps and gaps this enemy can actually make
+					let nav_graph = nav_graphs.get(&game_map, &NavSettings::for_actor(actor));
+					chase(enemy, actor, transform, player_position, nav_graph);
+				}
+				_ => wander(enemy, actor, transform, &game_map, &mut game_rng.rng, time.fixed_seconds()),
+			}
+		}
+	}
+}
+
+fn can_see(enemy: &Enemy, position: (f32, f32), player_position: (f32, f32), game_map: &GameMap) -> bool {
+	let dx = player_position.0 - position.0;
+	let dy = player_position.1 - position.1;
+
+	(dx * dx + dy * dy).sqrt() <= enemy.sight_range
+		&& collision::line_of_sight(game_map, position, player_position)
+}
+
+fn wander(
+	enemy: &mut Enemy,
+	actor: &mut Actor,
+	transform: &Transform,
+	game_map: &GameMap,
+	rng: &mut Pcg32,
+	delta_seconds: f32,
+) {
+	match enemy.behaviour {
+		EnemyBehaviour::IDLE => {
+			actor.h_input = 0.0;
+		}
+		EnemyBehaviour::JUMP => {
+			actor.h_input = 0.0;
 
 			if actor.standing {
-				let x: f64 = rng.gen();
+				let x: f32 = rng.gen();
 
-				if x < 0.05 {
-					actor.v_velocity = -600.0;
-					transform.translate_y(1.0);
+				if x < JUMPS_PER_SECOND * delta_seconds {
+					actor.v_velocity = actor.jump.velocity;
 				}
 			}
 		}
+		EnemyBehaviour::PATROL => {
+			patrol(enemy, actor, transform, game_map);
+		}
+	}
+}
+
+fn patrol(enemy: &mut Enemy, actor: &mut Actor, transform: &Transform, game_map: &GameMap) {
+	let hit_wall = (enemy.direction < 0.0 && actor.contacts.left)
+		|| (enemy.direction > 0.0 && actor.contacts.right);
+
+	// Ledges are only checked on the tile grid, moving platforms carry the enemy instead
+	let at_ledge = enemy.turn_at_ledges
+		&& actor.standing
+		&& actor.carried_by.is_none()
+		&& !collision::ground_ahead(game_map, &actor.rect(transform), enemy.direction);
+
+	if hit_wall || at_ledge {
+		enemy.direction = -enemy.direction;
+	}
+
+	actor.h_input = enemy.direction;
+}
+
+fn chase(enemy: &mut Enemy, actor: &mut Actor, transform: &Transform, player_position: (f32, f32), nav_graph: &NavGraph) {
+	let rect = actor.rect(transform);
+
+	// Pick the next link of the path while on the ground, keep heading for it while in the air
+	if actor.standing {
+		let start = nav_graph.nearest_node(rect.center_x(), rect.bottom() - 1.0);
+		let goal = nav_graph.nearest_node(player_position.0, player_position.1);
+
+		enemy.nav_link = match (start, goal) {
+			(Some(start), Some(goal)) => nav_graph
+				.find_path(start, goal)
+				.and_then(|path| path.first().cloned()),
+			_ => None,
+		};
+
+		if let Some(link) = enemy.nav_link {
+			if link.kind == NavLinkKind::JUMP {
+				actor.v_velocity = actor.jump.velocity;
+			}
+		}
+	}
+
+	let target_x = match enemy.nav_link {
+		Some(link) => (link.target.0 as f32 + 0.5) * nav_graph.tile_size as f32,
+		None => player_position.0,
+	};
+
+	let dx = target_x - rect.center_x();
+
+	if dx.abs() <= CHASE_DEAD_ZONE {
+		actor.h_input = 0.0;
+		return;
+	}
+
+	enemy.direction = dx.signum();
+	actor.h_input = enemy.direction;
+
+	// Without a path, jump over walls in the way
+	let hit_wall = (enemy.direction < 0.0 && actor.contacts.left)
+		|| (enemy.direction > 0.0 && actor.contacts.right);
+
+	if hit_wall && actor.standing && enemy.nav_link.is_none() {
+		actor.v_velocity = actor.jump.velocity;
 	}
 }
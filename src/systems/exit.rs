@@ -0,0 +1,37 @@
+use amethyst::core::Transform;
+use amethyst::ecs::{Join, ReadExpect, ReadStorage, System, Write};
+
+use crate::sidescroller::Actor;
+use crate::sidescroller::GameMap;
+use crate::sidescroller::LevelTransition;
+use crate::sidescroller::Player;
+
+pub struct ExitSystem;
+impl<'s> System<'s> for ExitSystem {
+	type SystemData = (
+		ReadStorage<'s, Transform>,
+		ReadStorage<'s, Player>,
+		ReadStorage<'s, Actor>,
+		ReadExpect<'s, GameMap>,
+		Write<'s, LevelTransition>,
+	);
+
+	fn run(&mut self, (transforms, players, actors, game_map, mut transition): Self::SystemData) {
+		if transition.target.is_some() {
+			return;
+		}
+
+		for (_, actor, transform) in (&players, &actors, &transforms).join() {
+			let x = transform.translation().x;
+			let y = transform.translation().y;
+
+			for exit in &game_map.exits {
+				if exit.overlaps(x, y, actor.width, actor.height) {
+					debug!(target: "game_engine", "Player entered exit to {:?}", exit.target_level);
+					transition.target = Some((exit.target_level.clone(), exit.target_spawn.clone()));
+					return;
+				}
+			}
+		}
+	}
+}
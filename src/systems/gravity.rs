@@ -4,6 +4,8 @@ use amethyst::ecs::{Join, Read, System, WriteStorage};
 
 use crate::sidescroller::Actor;
 
+pub const GRAVITY: f32 = 1000.0;
+
 pub struct GravitySystem;
 
 impl<'s> System<'s> for GravitySystem {
@@ -15,11 +17,19 @@ impl<'s> System<'s> for GravitySystem {
 
 	fn run(&mut self, (mut actors, mut locals, time): Self::SystemData) {
 		for (actor, local) in (&mut actors, &mut locals).join() {
-			local.translate_y(actor.v_velocity * time.delta_seconds());
+			let gravity = if actor.standing || actor.climbing { 0.0 } else { GRAVITY };
+			let (distance, velocity) = fall(actor.v_velocity, gravity, time.fixed_seconds());
 
-			if !actor.standing {
-				actor.v_velocity += 1000.0 * time.delta_seconds();
-			}
+			local.translate_y(distance);
+			actor.v_velocity = velocity;
 		}
 	}
 }
+
+/// Distance covered and the new velocity after falling for `delta_seconds`.
+/// This is exact for a constant acceleration, so the jump arc is the same at any frame rate.
+pub fn fall(velocity: f32, gravity: f32, delta_seconds: f32) -> (f32, f32) {
+	let distance = velocity * delta_seconds + 0.5 * gravity * delta_seconds * delta_seconds;
+
+	(distance, velocity + gravity * delta_seconds)
+}
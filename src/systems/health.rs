@@ -0,0 +1,99 @@
+use amethyst::core::timing::Time;
+use amethyst::core::Transform;
+use amethyst::ecs::prelude::{Resources, SystemData};
+use amethyst::ecs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::renderer::Hidden;
+use amethyst::shrev::{EventChannel, ReaderId};
+
+use crate::sidescroller::Actor;
+use crate::sidescroller::DamageEvent;
+use crate::sidescroller::Health;
+use crate::sidescroller::Player;
+
+/// How long the sprite stays hidden or shown while blinking.
+const BLINK_INTERVAL: f32 = 0.1;
+
+/// Applies damage events, blinks invulnerable actors, removes dead enemies and
+/// respawns dead players.
+#[derive(Default)]
+pub struct HealthSystem {
+	damage_reader: Option<ReaderId<DamageEvent>>,
+}
+
+impl<'s> System<'s> for HealthSystem {
+	type SystemData = (
+		Entities<'s>,
+		WriteStorage<'s, Health>,
+		WriteStorage<'s, Actor>,
+		WriteStorage<'s, Transform>,
+		WriteStorage<'s, Hidden>,
+		ReadStorage<'s, Player>,
+		Read<'s, EventChannel<DamageEvent>>,
+		Read<'s, Time>,
+	);
+
+	fn setup(&mut self, res: &mut Resources) {
+		Self::SystemData::setup(res);
+		self.damage_reader = Some(res.fetch_mut::<EventChannel<DamageEvent>>().register_reader());
+	}
+
+	fn run(
+		&mut self,
+		(entities, mut healths, mut actors, mut transforms, mut hiddens, players, damage_events, time): Self::SystemData,
+	) {
+		for event in damage_events.read(self.damage_reader.as_mut().unwrap()) {
+			if let Some(health) = healths.get_mut(event.target) {
+				if event.fatal {
+					health.current = 0;
+				} else if health.invulnerable_timer <= 0.0 && health.current > 0 {
+					health.current = health.current.saturating_sub(event.amount);
+					health.invulnerable_timer = health.invulnerable_time;
+
+					debug!(target: "game_engine", "{:?} took {} damage, health {}", event.target, event.amount, health.current);
+				}
+			}
+		}
+
+		for (entity, health, actor, transform) in (&entities, &mut healths, &mut actors, &mut transforms).join() {
+			if health.current == 0 {
+				if players.contains(entity) {
+					debug!(target: "game_engine", "Player died, respawning");
+
+					// Back to where the player entered the level
+					transform.set_x(actor.spawn.0);
+					transform.set_y(actor.spawn.1);
+
+					actor.position = None;
+					actor.previous_position = None;
+					actor.v_velocity = 0.0;
+					actor.h_velocity = 0.0;
+					actor.standing = false;
+					actor.climbing = false;
+					actor.carried_by = None;
+
+					health.current = health.max;
+					health.invulnerable_timer = health.invulnerable_time;
+				} else {
+					if let Err(err) = entities.delete(entity) {
+						error!(target: "game_engine", "Couldn't delete dead actor: {:?}", err);
+					}
+					continue;
+				}
+			}
+
+			health.invulnerable_timer = (health.invulnerable_timer - time.fixed_seconds()).max(0.0);
+
+			// Blink while invulnerable
+			let hidden = health.invulnerable_timer > 0.0
+				&& (health.invulnerable_timer / BLINK_INTERVAL) as u32 % 2 == 0;
+
+			if hidden && !hiddens.contains(entity) {
+				if let Err(err) = hiddens.insert(entity, Hidden) {
+					error!(target: "game_engine", "Couldn't hide blinking actor: {:?}", err);
+				}
+			} else if !hidden && hiddens.contains(entity) {
+				hiddens.remove(entity);
+			}
+		}
+	}
+}
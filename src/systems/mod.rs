@@ -1,9 +1,25 @@
+mod broadphase;
+mod camera;
+mod damage;
 mod player;
 mod gravity;
+mod health;
 mod actor;
 mod enemy;
+mod exit;
+mod movement;
+mod platform;
+mod spawner;
 
+pub use self::broadphase::BroadphaseSystem;
+pub use self::camera::CameraSystem;
+pub use self::damage::DamageSystem;
 pub use self::player::PlayerSystem;
-pub use self::gravity::GravitySystem;
+pub use self::gravity::{GravitySystem, GRAVITY};
+pub use self::health::HealthSystem;
 pub use self::actor::ActorSystem;
-pub use self::enemy::EnemySystem;
\ No newline at end of file
+pub use self::enemy::EnemySystem;
+pub use self::exit::ExitSystem;
+pub use self::movement::MovementSystem;
+pub use self::platform::PlatformSystem;
+pub use self::spawner::SpawnerSystem;
\ No newline at end of file
@@ -0,0 +1,53 @@
+use amethyst::core::timing::Time;
+use amethyst::core::Transform;
+use amethyst::ecs::{Join, Read, System, WriteStorage};
+
+use crate::sidescroller::Actor;
+
+/// Integrates the horizontal velocity of every actor from its `h_input`,
+/// accelerating towards the max speed and decelerating with friction.
+pub struct MovementSystem;
+
+impl<'s> System<'s> for MovementSystem {
+	type SystemData = (
+		WriteStorage<'s, Actor>,
+		WriteStorage<'s, Transform>,
+		Read<'s, Time>,
+	);
+
+	fn run(&mut self, (mut actors, mut locals, time): Self::SystemData) {
+		for (actor, local) in (&mut actors, &mut locals).join() {
+			let control = if actor.standing { 1.0 } else { actor.air_control };
+			let target = actor.h_input * actor.max_speed;
+
+			// Accelerate while pushing in the direction of travel, otherwise slow down
+			let rate = if actor.h_input != 0.0 && actor.h_velocity * target >= 0.0 {
+				actor.acceleration
+			} else {
+				actor.deceleration
+			};
+
+			let (distance, velocity) = integrate(actor.h_velocity, target, rate * control, time.fixed_seconds());
+
+			local.translate_x(distance);
+			actor.h_velocity = velocity;
+		}
+	}
+}
+
+/// Distance covered and the new velocity after moving towards the target
+/// velocity at the given rate for `delta_seconds`.
+pub fn integrate(velocity: f32, target: f32, rate: f32, delta_seconds: f32) -> (f32, f32) {
+	let new_velocity = approach(velocity, target, rate * delta_seconds);
+
+	// Average of the old and new velocity keeps the distance independent of the frame rate
+	((velocity + new_velocity) / 2.0 * delta_seconds, new_velocity)
+}
+
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+	if value < target {
+		(value + step).min(target)
+	} else {
+		(value - step).max(target)
+	}
+}
@@ -0,0 +1,67 @@
+use amethyst::core::timing::Time;
+use amethyst::core::Transform;
+use amethyst::ecs::{Join, Read, System, WriteStorage};
+
+use crate::sidescroller::Platform;
+
+/// Moves the platforms along their paths, looping or going back and forth.
+pub struct PlatformSystem;
+
+impl<'s> System<'s> for PlatformSystem {
+	type SystemData = (
+		WriteStorage<'s, Platform>,
+		WriteStorage<'s, Transform>,
+		Read<'s, Time>,
+	);
+
+	fn run(&mut self, (mut platforms, mut transforms, time): Self::SystemData) {
+		for (platform, transform) in (&mut platforms, &mut transforms).join() {
+			let start = (transform.translation().x, transform.translation().y);
+			let mut position = start;
+			let mut distance = platform.speed * time.fixed_seconds();
+
+			// Path points can be closer together than a tick of movement
+			while distance > 0.0 && platform.path.len() > 1 {
+				let target = platform.path[platform.target];
+				let to_target = (target.0 - position.0, target.1 - position.1);
+				let length = (to_target.0 * to_target.0 + to_target.1 * to_target.1).sqrt();
+
+				if length > distance {
+					position.0 += to_target.0 / length * distance;
+					position.1 += to_target.1 / length * distance;
+					break;
+				}
+
+				position = target;
+				distance -= length;
+				next_target(platform);
+			}
+
+			platform.delta = (position.0 - start.0, position.1 - start.1);
+
+			transform.set_x(position.0);
+			transform.set_y(position.1);
+		}
+	}
+}
+
+fn next_target(platform: &mut Platform) {
+	let last = platform.path.len() - 1;
+
+	if !platform.ping_pong {
+		platform.target = if platform.target == last { 0 } else { platform.target + 1 };
+		return;
+	}
+
+	if platform.target == last {
+		platform.reversing = true;
+	} else if platform.target == 0 {
+		platform.reversing = false;
+	}
+
+	if platform.reversing {
+		platform.target -= 1;
+	} else {
+		platform.target += 1;
+	}
+}
@@ -1,66 +1,115 @@
+use amethyst::core::timing::Time;
 use amethyst::core::Transform;
-use amethyst::ecs::{Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::ecs::{Join, Read, ReadExpect, ReadStorage, System, WriteStorage};
 use amethyst::input::InputHandler;
-use amethyst::renderer::Camera;
 
+use crate::collision;
+use crate::collision::Rect;
 use crate::sidescroller::Actor;
+use crate::sidescroller::GameMap;
 use crate::sidescroller::Player;
 
-const MOVEMENT_SCALE: f32 = 3.0;
+/// How long one-way platforms are ignored after dropping through one.
+const DROP_TIME: f32 = 0.2;
 
 pub struct PlayerSystem;
 impl<'s> System<'s> for PlayerSystem {
 	type SystemData = (
-		WriteStorage<'s, Transform>,
+		ReadStorage<'s, Transform>,
 		WriteStorage<'s, Player>,
 		WriteStorage<'s, Actor>,
-		ReadStorage<'s, Camera>,
+		ReadExpect<'s, GameMap>,
 		Read<'s, InputHandler<String, String>>,
+		Read<'s, Time>,
 	);
 
 	fn run(
 		&mut self,
-		(mut transforms, mut players, mut actors, camera, input): Self::SystemData,
+		(transforms, mut players, mut actors, game_map, input, time): Self::SystemData,
 	) {
 		let movement = input.axis_value("running");
-		let mut player_x = 0.0;
-		let mut player_y = 0.0;
+		let vertical = input.axis_value("vertical").unwrap_or(0.0).max(-1.0).min(1.0) as f32;
+		let jump_down = input.action_is_down("jumping").unwrap_or(false);
 
-		for (_, actor, transform) in (&mut players, &mut actors, &mut transforms).join() {
-			if let Some(mv_amount) = movement {
-				let scaled_amount = MOVEMENT_SCALE * mv_amount as f32;
+		for (player, actor, transform) in (&mut players, &mut actors, &transforms).join() {
+			actor.h_input = movement.unwrap_or(0.0).max(-1.0).min(1.0) as f32;
 
-				transform.translate_x(scaled_amount);
-				
+			let mut jump_pressed = jump_down && !player.jump_was_down;
+			player.jump_was_down = jump_down;
+
+			actor.drop_timer = (actor.drop_timer - time.fixed_seconds()).max(0.0);
+
+			// Extended one pixel down so a ladder can be climbed down from its top
+			let ladder_rect = Rect::from_center(
+				transform.translation().x,
+				transform.translation().y,
+				actor.width,
+				actor.height + 1.0,
+			);
+			let on_ladder = collision::overlaps_tiles(&game_map, &ladder_rect, |attributes| attributes.ladder);
+
+			if !on_ladder {
+				actor.climbing = false;
+			} else if vertical != 0.0 && !actor.climbing {
+				actor.climbing = true;
+				actor.jumping = false;
 			}
 
-			if let Some(is_jumping) = input.action_is_down("jumping") {
-				if is_jumping && actor.standing {
-					actor.v_velocity = -600.0;
-					transform.translate_y(1.0);
+			if actor.climbing {
+				actor.v_velocity = vertical * actor.climb_speed;
+
+				// Jumping off detaches from the ladder
+				if jump_pressed {
+					actor.climbing = false;
+					actor.v_velocity = actor.jump.velocity;
+					actor.jumping = true;
 				}
+
+				continue;
 			}
 
-			player_x = transform.translation().x;
-			player_y = transform.translation().y;
+			// Down and jump together drops through one-way platforms instead of jumping,
+			// on anything else it is an ordinary jump
+			let rect = actor.rect(transform);
+			let below_feet = Rect::new(rect.left(), rect.bottom(), rect.width, 1.0);
+			let on_one_way = collision::overlaps_tiles(&game_map, &below_feet, |attributes| attributes.one_way && !attributes.solid);
 
-			// debug!(target: "game_engine",
-			// 	"Player coordinates: {} {}",
-			// 	transform.translation[0], transform.translation[1]
-			// );
-		}
+			if jump_pressed && vertical > 0.0 && actor.standing && on_one_way {
+				actor.drop_timer = DROP_TIME;
+				jump_pressed = false;
+			}
+
+			// Coyote time: the jump stays available for a moment after leaving a ledge
+			if actor.standing && actor.drop_timer <= 0.0 {
+				actor.coyote_timer = actor.jump.coyote_time;
+			} else {
+				actor.coyote_timer = (actor.coyote_timer - time.fixed_seconds()).max(0.0);
+			}
 
-		for (_, transform) in (&camera, &mut transforms).join() {
+			// Jump buffering: a press just before landing still jumps on landing
+			if jump_pressed {
+				actor.jump_buffer_timer = actor.jump.buffer_time;
+			} else {
+				actor.jump_buffer_timer = (actor.jump_buffer_timer - time.fixed_seconds()).max(0.0);
+			}
+
+			if actor.jump_buffer_timer > 0.0 && actor.coyote_timer > 0.0 {
+				// Airborne from this tick on, so gravity and air control apply to the jump right away
+				actor.v_velocity = actor.jump.velocity;
+				actor.standing = false;
+				actor.jump_buffer_timer = 0.0;
+				actor.coyote_timer = 0.0;
+				actor.jumping = true;
+			}
 
-			// Place camera view so that the player is in the middle
-			transform.set_x(player_x - (800.0/2.0));
-			transform.set_y(player_y - (600.0/2.0));
-			if transform.translation().x < 0.0 {
-				transform.set_x(0.0);
+			// Releasing the jump early cuts it short
+			if actor.jumping && !jump_down && actor.v_velocity < 0.0 {
+				actor.v_velocity *= actor.jump.cut_multiplier;
+				actor.jumping = false;
 			}
 
-			if transform.translation().y < 0.0 {
-				transform.set_y(0.0);
+			if actor.v_velocity >= 0.0 {
+				actor.jumping = false;
 			}
 		}
 	}
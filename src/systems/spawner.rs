@@ -0,0 +1,71 @@
+use amethyst::core::timing::Time;
+use amethyst::core::Transform;
+use amethyst::ecs::{Entities, Join, LazyUpdate, Read, ReadStorage, System, WriteStorage};
+
+use crate::sidescroller;
+use crate::sidescroller::Player;
+use crate::sidescroller::Spawner;
+
+/// Spawns enemies from the spawners near the player, keeping at most `max_alive`
+/// of them around and stopping after `total`. New enemies are built through
+/// `LazyUpdate` and show up after the next world maintenance.
+pub struct SpawnerSystem;
+impl<'s> System<'s> for SpawnerSystem {
+	type SystemData = (
+		Entities<'s>,
+		ReadStorage<'s, Transform>,
+		ReadStorage<'s, Player>,
+		WriteStorage<'s, Spawner>,
+		Read<'s, LazyUpdate>,
+		Read<'s, Time>,
+	);
+
+	fn run(&mut self, (entities, transforms, players, mut spawners, lazy, time): Self::SystemData) {
+		let player_position = (&players, &transforms)
+			.join()
+			.map(|(_, transform)| (transform.translation().x, transform.translation().y))
+			.next();
+
+		for (spawner, transform) in (&mut spawners, &transforms).join() {
+			spawner.alive.retain(|enemy| entities.is_alive(*enemy));
+
+			if spawner.is_exhausted() || spawner.alive.len() >= spawner.max_alive {
+				continue;
+			}
+
+			let position = (transform.translation().x, transform.translation().y);
+
+			let active = spawner.radius <= 0.0
+				|| player_position.map_or(false, |player_position| {
+					let dx = player_position.0 - position.0;
+					let dy = player_position.1 - position.1;
+
+					(dx * dx + dy * dy).sqrt() <= spawner.radius
+				});
+
+			if !active {
+				continue;
+			}
+
+			spawner.timer -= time.fixed_seconds();
+
+			if spawner.timer > 0.0 {
+				continue;
+			}
+
+			let enemy = sidescroller::build_enemy(
+				lazy.create_entity(&entities),
+				&spawner.archetype,
+				&spawner.properties,
+				position,
+				spawner.sprite_sheet.clone(),
+			);
+
+			debug!(target: "game_engine", "Spawner at {:?} spawned {:?}", position, enemy);
+
+			spawner.alive.push(enemy);
+			spawner.spawned += 1;
+			spawner.timer = spawner.interval;
+		}
+	}
+}